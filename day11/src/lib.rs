@@ -1,6 +1,6 @@
 use std::collections::HashMap;
-use std::sync::{mpsc};
-use std::thread;
+
+use intcode::Status;
 
 #[derive(std::fmt::Debug)]
 pub enum Color {
@@ -25,19 +25,19 @@ struct State{
     panels: HashMap<(i32, i32), Color>,
 }
 
-pub fn run_simulation_part1(opcodes: &Vec<i64>, init_color:Color) -> usize {
+pub fn run_simulation_part1(opcodes: &[i64], init_color:Color) -> usize {
     let state = run_robot(opcodes, init_color);
 
     state.panels.len()
 }
 
-pub fn run_simulation_part2(opcodes: &Vec<i64>, init_color:Color) -> Vec<Vec<char>> {
+pub fn run_simulation_part2(opcodes: &[i64], init_color:Color) -> Vec<Vec<char>> {
     let state = run_robot(opcodes, init_color);
 
     let mut lines:Vec<Vec<char>> = Vec::new();
     for ((x,y), color) in state.panels.iter() {
         if lines.len() < *y as usize + 1 {
-            lines.resize_with(*y as usize + 1, || Vec::new())
+            lines.resize_with(*y as usize + 1, Vec::new)
         }
         let line: &mut Vec<char> = lines[*y as usize].as_mut();
         if line.len() < *x as usize + 1 {
@@ -49,7 +49,7 @@ pub fn run_simulation_part2(opcodes: &Vec<i64>, init_color:Color) -> Vec<Vec<cha
     lines
 }
 
-fn run_robot(opcodes: &Vec<i64>, init_color: Color) -> State {
+fn run_robot(opcodes: &[i64], init_color: Color) -> State {
     fn step(input: i64, state: &mut State) {
         state.direction = match (input, &state.direction) {
             (0, Direction::Up) => Direction::Left,
@@ -83,8 +83,6 @@ fn run_robot(opcodes: &Vec<i64>, init_color: Color) -> State {
     }
 
 
-    let (input_send, input) = mpsc::channel();
-    let (output, output_recieve) = mpsc::channel();
     let mut state = State {
         x: 0,
         y: 0,
@@ -92,20 +90,24 @@ fn run_robot(opcodes: &Vec<i64>, init_color: Color) -> State {
         panels: HashMap::new(),
     };
     state.panels.insert((0, 0), init_color);
-    let mut context = intcode::Context::new(opcodes.to_vec(), input, output);
-    thread::spawn(move || {
-        intcode::run(&mut context);
-    });
+    let mut context = intcode::Context::with_memory(opcodes.to_vec());
+    let mut paint_command = None;
     loop {
-        input_send.send(match read_color(&state) {
-            Color::Black => 0,
-            Color::White => 1
-        }).unwrap();
-        let paint_command = output_recieve.recv();
-        if paint_command.is_err() { break }
-        paint(paint_command.unwrap(), &mut state);
-        step(output_recieve.recv().unwrap(), &mut state);
-        //println!("x:{}, y:{}, new{:#?}", &state.x, &state.y, paint_command.unwrap())
+        match context.resume().unwrap() {
+            Status::NeedsInput => context.push_input(match read_color(&state) {
+                Color::Black => 0,
+                Color::White => 1
+            }),
+            Status::Output(value) => match paint_command.take() {
+                None => paint_command = Some(value),
+                Some(color) => {
+                    paint(color, &mut state);
+                    step(value, &mut state);
+                    //println!("x:{}, y:{}, new{:#?}", &state.x, &state.y, color)
+                }
+            },
+            Status::Halted => break
+        }
     }
     state
 }
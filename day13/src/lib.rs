@@ -1,43 +1,47 @@
-use std::sync::{mpsc};
-use std::sync::mpsc::{Receiver, Sender};
 use std::thread;
 
+use intcode::{Context, Status};
+
 use termion::{clear,cursor};
 use std::time::Duration;
 
-pub fn run_simulation_part1(opcodes: &mut Vec<i64>, display: bool, delay: u64) -> usize {
+pub fn run_simulation_part1(opcodes: &mut [i64], display: bool, delay: u64) -> usize {
     run_simulation(opcodes, display, delay).0
 }
 
-pub fn run_simulation_part2(opcodes: &mut Vec<i64>, display: bool, delay: u64) -> i64 {
+pub fn run_simulation_part2(opcodes: &mut [i64], display: bool, delay: u64) -> i64 {
     (*opcodes)[0] = 2;
     run_simulation(opcodes, display, delay).1
 }
 
-pub fn run_simulation(opcodes: &mut Vec<i64>, display: bool, delay: u64) -> (usize, i64){
-    let (input_send, input) = mpsc::channel();
-    let (output, output_recieve) = mpsc::channel();
-
-    run_intcode_computer(opcodes, input, output);
-    game_loop(input_send, output_recieve, display, delay)
+pub fn run_simulation(opcodes: &[i64], display: bool, delay: u64) -> (usize, i64){
+    let mut context = Context::with_memory(opcodes.to_vec());
+    game_loop(&mut context, display, delay)
 }
 
-fn game_loop(input_send: Sender<i64>, output_recieve: Receiver<i64>, display: bool, delay: u64) -> (usize, i64) {
+fn game_loop(context: &mut Context, display: bool, delay: u64) -> (usize, i64) {
     let mut score = 0;
     let mut block_count = 0;
     let mut paddle_position = 0;
     let mut ball_position = 0;
     let mut iteration = 0;
+    let mut tile = Vec::with_capacity(3);
 
     if display { print!("{}", clear::All); }
     loop {
-        let xpos = output_recieve.recv();
-        if xpos.is_err() {
-            break;
+        match context.resume().unwrap() {
+            Status::NeedsInput => {
+                context.push_input(new_movement(paddle_position, ball_position));
+                continue;
+            }
+            Status::Output(value) => tile.push(value),
+            Status::Halted => break,
         }
-        let xpos = xpos.unwrap();
-        let ypos = output_recieve.recv().unwrap();
-        let action = output_recieve.recv().unwrap();
+        if tile.len() < 3 {
+            continue;
+        }
+        let (xpos, ypos, action) = (tile[0], tile[1], tile[2]);
+        tile.clear();
 
         if display { print!("{}", cursor::Goto((xpos + 1) as u16, (ypos + 1) as u16)); }
         match action {
@@ -58,7 +62,6 @@ fn game_loop(input_send: Sender<i64>, output_recieve: Receiver<i64>, display: bo
             4 => {
                 ball_position = xpos;
                 let new_direction = new_movement(paddle_position, ball_position);
-                input_send.send(new_direction);
                 if display { println!("o"); }
                 iteration +=1;
                 if display { println!("{}Update movement ball ({} {}) paddle {} new direction {} score {}      ", cursor::Goto(1,iteration % 10 + 25 ), ball_position, ypos, paddle_position, new_direction, score); }
@@ -80,13 +83,6 @@ fn new_movement(paddle_position: i64, ball_position: i64) -> i64 {
     else { 0 }
 }
 
-fn run_intcode_computer(opcodes: &mut Vec<i64>, input: Receiver<i64>, output: Sender<i64>) {
-    let mut context = intcode::Context::new(opcodes.to_vec(), input, output);
-    thread::spawn(move || {
-        intcode::run(&mut context);
-    });
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
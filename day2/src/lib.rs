@@ -1,19 +1,88 @@
-use std::sync::{mpsc};
-use std::thread;
-
 use intcode::*;
 
-pub fn day2(opcodes: &Vec<i64>) -> i64 {
-    let (_, input) = mpsc::channel();
-    let (output, _) = mpsc::channel();
-    let mut context = intcode::Context::new(opcodes.to_vec(), input, output);
-    thread::spawn(move || {
-        run(&mut context);
-        context.read(0)
-    }).join().unwrap_or_default()
+/// Cell 0 of memory once the program halts. Panics with the exit status when the program
+/// faults or asks for input instead.
+pub fn day2(opcodes: &[i64]) -> i64 {
+    let execution = execute(opcodes, &[], &[]);
+    assert!(execution.halted(), "program stopped at {} with {:?}", execution.ip, execution.status);
+    execution.read(0)
+}
+
+/// Nouns and verbs that are tried, for both cells.
+pub const INPUT_RANGE: std::ops::RangeInclusive<i64> = 0..=99;
+
+/// Instructions a run may execute, so a noun and verb that make the program loop do not
+/// stall the search.
+pub const MAX_INSTRUCTIONS: u64 = 100_000;
+
+/// Output of the program with `noun` and `verb` in cells 1 and 2, `None` when it faults or
+/// runs into `MAX_INSTRUCTIONS`.
+pub fn run_with(opcodes: &[i64], noun: i64, verb: i64) -> Option<i64> {
+    let limits = Limits { max_instructions: Some(MAX_INSTRUCTIONS), ..Limits::default() };
+    let execution = execute_with_limits(opcodes, &[(1, noun), (2, verb)], &[], limits);
+    Some(execution.read(0)).filter(|_| execution.halted())
+}
+
+/// Every noun and verb giving `target`, trying all of them on all available threads.
+pub fn brute_force(opcodes: &[i64], target: i64) -> Vec<(i64, i64)> {
+    let nouns: Vec<i64> = INPUT_RANGE.collect();
+    let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    let mut solutions: Vec<(i64, i64)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = nouns.chunks(nouns.len().div_ceil(threads)).map(|nouns| scope.spawn(move || {
+            nouns.iter()
+                .flat_map(|&noun| INPUT_RANGE.map(move |verb| (noun, verb)))
+                .filter(|&(noun, verb)| run_with(opcodes, noun, verb) == Some(target))
+                .collect::<Vec<(i64, i64)>>()
+        })).collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    });
+    solutions.sort_unstable();
+    solutions
+}
+
+/// Solves `target` without searching when the output is `constant + noun * a + verb * b`.
+/// The dependence is derived from three runs and confirmed by a few more; `None` when the
+/// output is not affine, the program faults on a probe or the arithmetic overflows.
+pub fn solve_affine(opcodes: &[i64], target: i64) -> Option<Vec<(i64, i64)>> {
+    let constant = run_with(opcodes, 0, 0)?;
+    let a = run_with(opcodes, 1, 0)?.checked_sub(constant)?;
+    let b = run_with(opcodes, 0, 1)?.checked_sub(constant)?;
+    for &(noun, verb) in &[(1i64, 1i64), (2, 3), (37, 58), (99, 99)] {
+        let expected = constant.checked_add(noun.checked_mul(a)?)?.checked_add(verb.checked_mul(b)?)?;
+        if run_with(opcodes, noun, verb)? != expected {
+            return None;
+        }
+    }
+    let mut solutions = Vec::new();
+    for noun in INPUT_RANGE {
+        let rest = target.checked_sub(constant)?.checked_sub(noun.checked_mul(a)?)?;
+        match b {
+            0 if rest == 0 => solutions.extend(INPUT_RANGE.map(|verb| (noun, verb))),
+            0 => {}
+            b => {
+                let verb = rest.checked_div(b)?;
+                if rest.checked_rem(b)? == 0 && INPUT_RANGE.contains(&verb) {
+                    solutions.push((noun, verb));
+                }
+            }
+        }
+    }
+    Some(solutions)
+}
+
+/// Every noun and verb giving `target`: the affine solution when it finds solutions and
+/// every one of them checks out, otherwise a brute force search. The affine solution
+/// assumes the output is affine everywhere, not only at the probes, so it misses solutions
+/// of a program that merely looks affine there.
+pub fn solve(opcodes: &[i64], target: i64) -> Vec<(i64, i64)> {
+    match solve_affine(opcodes, target) {
+        Some(solutions) if !solutions.is_empty() && solutions.iter().all(|&(noun, verb)| run_with(opcodes, noun, verb) == Some(target)) => solutions,
+        _ => brute_force(opcodes, target),
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
     use super::*;
 
@@ -38,6 +107,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::zero_prefixed_literal)]
     fn test_day2_part1_assignment() {
         let mut memory = read_program_from_file("input2.txt");
         memory[1]=12;
@@ -46,12 +116,40 @@ mod tests {
         assert_eq!(day2(&memory), 4138687);
     }
 
+    #[test]
+    #[should_panic(expected = "program stopped at 0 with Faulted(UnknownOpcode { ip: 0, opcode: 42 })")]
+    fn test_day2_part1_fault() {
+        day2(&[42]);
+    }
+
+    #[test]
+    fn test_day2_part2_solvers() {
+        // Outputs noun + verb and noun * verb.
+        assert_eq!(solve_affine(&[1101, 0, 0, 0, 99], 2), Some(vec!((0, 2), (1, 1), (2, 0))));
+        assert_eq!(solve_affine(&[1102, 0, 0, 0, 99], 12), None);
+        assert_eq!(solve(&[1102, 0, 0, 0, 99], 12), vec!((1, 12), (2, 6), (3, 4), (4, 3), (6, 2), (12, 1)));
+        // Outputs (noun + verb) << 61, which overflows the affine arithmetic.
+        let program = [1101, 0, 0, 5, 1002, 5, 1 << 61, 0, 99];
+        assert_eq!(solve_affine(&program, 0), None);
+        assert_eq!(solve(&program, 0), brute_force(&program, 0));
+        // Outputs -(noun + verb); solving for i64::MIN divides it by -1.
+        let program = [1101, 0, 0, 12, 1002, 12, -1, 0, 99, 0, 0, 0, 0];
+        assert_eq!(solve_affine(&program, i64::MIN), None);
+        assert_eq!(solve(&program, i64::MIN), vec!());
+        // Loops forever for a noun other than 0 and verb 0.
+        assert_eq!(run_with(&[1105, 0, 0, 99], 1, 0), None);
+        assert_eq!(run_with(&[1105, 0, 0, 99], 0, 0), Some(1105));
+    }
+
     #[test]
     fn test_day2_part2_assignment() {
-        let mut memory = read_program_from_file("input2.txt");
-        memory[1]=66;
-        memory[2]=35;
-        day2(&memory);
-        assert_eq!(day2(&memory), 19690720);
+        let memory = read_program_from_file("input2.txt");
+        assert_eq!(solve(&memory, 19690720), vec!((66, 35)));
+    }
+
+    #[test]
+    fn test_day2_part2_assignment_brute_force() {
+        let memory = read_program_from_file("input2.txt");
+        assert_eq!(brute_force(&memory, 19690720), vec!((66, 35)));
     }
 }
@@ -1,4 +1,5 @@
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
     #[test]
     fn test_day5_part1_opcode030499() {
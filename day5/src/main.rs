@@ -1,3 +1,4 @@
+#[allow(clippy::useless_vec)]
 fn main() {
     let memory = intcode::read_program_from_file("input5.txt");
 
@@ -1,13 +1,10 @@
-use std::sync::{mpsc};
-use std::sync::mpsc::{Receiver, Sender};
-use std::thread;
-use std::thread::JoinHandle;
+use intcode::Topology;
 
-pub fn run_simulation(opcodes: &Vec<i64>, phases: Vec<usize>) -> (Vec<usize>, i64) {
+pub fn run_simulation(opcodes: &[i64], phases: Vec<usize>) -> (Vec<usize>, i64) {
     recurse(phases)
         .iter()
         .fold(None, |acc: Option<(Vec<usize>, i64)>, phases| {
-            let result = internal(opcodes, &phases);
+            let result = internal(opcodes, phases);
             match acc {
                 Some((p, high)) if high >= result => Some((p, high)),
                 _ => Some((phases.clone(), result)),
@@ -15,49 +12,18 @@ pub fn run_simulation(opcodes: &Vec<i64>, phases: Vec<usize>) -> (Vec<usize>, i6
         }).unwrap()
 }
 
-fn internal(opcodes: &Vec<i64>, phases:&Vec<usize>) -> i64 {
-    let (sender0, reciever1) = mpsc::channel();
-    let (sender1, reciever2) = mpsc::channel();
-    let (sender2, reciever3) = mpsc::channel();
-    let (sender3, reciever4) = mpsc::channel();
-    let (sender4, reciever5) = mpsc::channel();
-    let (sender5, reciever0) = mpsc::channel();
-
-    init_amplifier(opcodes, sender1.clone(), reciever1);
-    init_amplifier(opcodes, sender2.clone(), reciever2);
-    init_amplifier(opcodes, sender3.clone(), reciever3);
-    init_amplifier(opcodes, sender4.clone(), reciever4);
-    init_amplifier(opcodes, sender5.clone(), reciever5);
-
-    sender4.send(phases[4] as i64).unwrap();
-    sender3.send(phases[3] as i64).unwrap();
-    sender2.send(phases[2] as i64).unwrap();
-    sender1.send(phases[1] as i64).unwrap();
-    sender0.send(phases[0] as i64).unwrap();
-    sender0.send(0).unwrap();
-
-    std::mem::drop(sender1);
-    std::mem::drop(sender2);
-    std::mem::drop(sender3);
-    std::mem::drop(sender4);
-    std::mem::drop(sender5);
-
-    let mut result = 0;
-    reciever0.iter().for_each(|n| {
-//        println!("iter {}", n);
-        result = n;
-        sender0.send(n).ok();
-    });
-
-    result
-}
-
-fn init_amplifier(opcodes: &Vec<i64>, sender: Sender<i64>, reciever :Receiver<i64>) -> JoinHandle<()> {
-    let mut context = intcode::Context::new(opcodes.to_vec(), reciever, sender);
-//    println!("init amplifier");
-    thread::spawn(move || {
-        intcode::run(&mut context);
-    })
+/// Runs one amplifier per phase, connected in a feedback loop. Without feedback the first
+/// amplifier has halted before the last one outputs, so this covers the plain chain as well.
+fn internal(opcodes: &[i64], phases: &[usize]) -> i64 {
+    let mut topology = Topology::new();
+    let phases: Vec<Vec<i64>> = phases.iter().map(|&phase| vec!(phase as i64)).collect();
+    let amplifiers = topology.machines(opcodes, &phases);
+    topology.input(amplifiers[0], &[0])
+        .cycle(&amplifiers)
+        .tap("thrusters", amplifiers[amplifiers.len() - 1]);
+    let mut network = topology.build();
+    network.run().unwrap();
+    network.last("thrusters").unwrap()
 }
 
 pub fn recurse(input: Vec<usize>) -> Vec<Vec<usize>> {
@@ -77,6 +43,7 @@ pub fn recurse(input: Vec<usize>) -> Vec<Vec<usize>> {
     }
 }
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
     use super::*;
 
@@ -1,4 +1,5 @@
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
     #[test]
     fn test_day9_part1_example1() {
@@ -10,6 +11,14 @@ mod tests {
         assert_eq!(intcode::run_input_output(&vec!(1102,34915192,34915192,7,4,7,99,0), &vec!()), vec!(1219070632396864));
     }
 
+    #[test]
+    fn test_day9_part1_example2_checked() {
+        let mut context = intcode::Context::new(vec!(1102,34915192,34915192,7,4,7,99,0), (), Vec::new());
+        context.set_arithmetic_policy(intcode::ArithmeticPolicy::Checked);
+        intcode::run(&mut context).unwrap();
+        assert_eq!(context.into_output(), vec!(1219070632396864));
+    }
+
     #[test]
     fn test_day9_part1_example3() {
         assert_eq!(intcode::run_input_output(&vec!(104,1125899906842624,99), &vec!()), vec!(1125899906842624));
@@ -21,6 +30,15 @@ mod tests {
         assert_eq!(intcode::run_input_output(&memory, &vec!(1)), vec!(3235019597));
     }
 
+    #[test]
+    fn test_day9_part1_assignment_checked() {
+        let memory = intcode::read_program_from_file("input9.txt");
+        let mut context = intcode::Context::new(memory, std::collections::VecDeque::from(vec!(1)), Vec::new());
+        context.set_arithmetic_policy(intcode::ArithmeticPolicy::Checked);
+        intcode::run(&mut context).unwrap();
+        assert_eq!(context.into_output(), vec!(3235019597));
+    }
+
     #[test]
     fn test_day9_part2_assignment() {
         let memory = intcode::read_program_from_file("input9.txt");
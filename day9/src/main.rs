@@ -1,3 +1,4 @@
+#[allow(clippy::useless_vec)]
 fn main() {
     let memory = intcode::read_program_from_file("input9.txt");
     let outputs = intcode::run_input_output(&memory, &vec!(1));
@@ -0,0 +1,140 @@
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 20;
+
+/// The interpreter as it decoded instructions before the static dispatch table: a hash map
+/// lookup per instruction and a vector of modes and of parameters allocated for each one.
+/// Kept as the baseline the library is compared against.
+mod baseline {
+    use std::collections::HashMap;
+
+    enum Parameter {
+        Absolute(i64),
+        Immediate(i64),
+        Relative(i64),
+    }
+
+    struct Machine {
+        memory: Vec<i64>,
+        relative_base: i64,
+        input: Vec<i64>,
+        output: Vec<i64>,
+    }
+
+    impl Machine {
+        fn position(&mut self, parameter: &Parameter) -> usize {
+            let position = match *parameter {
+                Parameter::Absolute(position) => position as usize,
+                Parameter::Relative(offset) => (self.relative_base + offset) as usize,
+                Parameter::Immediate(_) => 0,
+            };
+            if position >= self.memory.len() {
+                self.memory.resize(position + 1, 0);
+            }
+            position
+        }
+
+        fn read(&mut self, parameter: &Parameter) -> i64 {
+            match *parameter {
+                Parameter::Immediate(value) => value,
+                _ => {
+                    let position = self.position(parameter);
+                    self.memory[position]
+                }
+            }
+        }
+
+        fn write(&mut self, parameter: &Parameter, value: i64) {
+            let position = self.position(parameter);
+            self.memory[position] = value;
+        }
+    }
+
+    fn split_instruction(opcode: usize) -> (usize, Vec<usize>) {
+        let mut modes = Vec::new();
+        let mut digits = opcode / 100;
+        while digits > 0 {
+            modes.push(digits % 10);
+            digits /= 10;
+        }
+        (opcode % 100, modes)
+    }
+
+    pub fn run_input_output(program: &[i64], inputs: &[i64]) -> Vec<i64> {
+        let operand_counts: HashMap<usize, usize> =
+            vec!((1, 3), (2, 3), (3, 1), (4, 1), (5, 2), (6, 2), (7, 3), (8, 3), (9, 1), (99, 0)).into_iter().collect();
+        let mut machine = Machine { memory: program.to_vec(), relative_base: 0, input: inputs.iter().rev().copied().collect(), output: Vec::new() };
+        let mut ip = 0;
+        loop {
+            let (opcode, modes) = split_instruction(machine.memory[ip] as usize);
+            let parameters: Vec<Parameter> = (0..operand_counts[&opcode]).map(|index| {
+                let value = machine.memory[ip + 1 + index];
+                match modes.get(index) {
+                    Some(1) => Parameter::Immediate(value),
+                    Some(2) => Parameter::Relative(value),
+                    _ => Parameter::Absolute(value),
+                }
+            }).collect();
+            ip += 1 + parameters.len();
+            match opcode {
+                1 => {
+                    let value = machine.read(&parameters[0]) + machine.read(&parameters[1]);
+                    machine.write(&parameters[2], value);
+                }
+                2 => {
+                    let value = machine.read(&parameters[0]) * machine.read(&parameters[1]);
+                    machine.write(&parameters[2], value);
+                }
+                3 => {
+                    let value = machine.input.pop().unwrap();
+                    machine.write(&parameters[0], value);
+                }
+                4 => {
+                    let value = machine.read(&parameters[0]);
+                    machine.output.push(value);
+                }
+                5 => if machine.read(&parameters[0]) != 0 {
+                    ip = machine.read(&parameters[1]) as usize;
+                },
+                6 => if machine.read(&parameters[0]) == 0 {
+                    ip = machine.read(&parameters[1]) as usize;
+                },
+                7 => {
+                    let value = (machine.read(&parameters[0]) < machine.read(&parameters[1])) as i64;
+                    machine.write(&parameters[2], value);
+                }
+                8 => {
+                    let value = (machine.read(&parameters[0]) == machine.read(&parameters[1])) as i64;
+                    machine.write(&parameters[2], value);
+                }
+                9 => machine.relative_base += machine.read(&parameters[0]),
+                _ => return machine.output,
+            }
+        }
+    }
+}
+
+/// Average time per run in milliseconds.
+fn measure(run: fn(&[i64], &[i64]) -> Vec<i64>, program: &[i64], input: i64, expected: i64) -> f64 {
+    let mut total = Duration::new(0, 0);
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        let outputs = run(program, &[input]);
+        total += start.elapsed();
+        assert_eq!(outputs, vec!(expected));
+    }
+    total.as_secs_f64() * 1000.0 / ITERATIONS as f64
+}
+
+fn compare(name: &str, program: &[i64], input: i64, expected: i64) {
+    let before = measure(baseline::run_input_output, program, input, expected);
+    let after = measure(intcode::run_input_output, program, input, expected);
+    println!("{:<16} baseline {:>8.3} ms/run, intcode {:>8.3} ms/run, {:.1}x ({} runs)",
+             name, before, after, before / after, ITERATIONS);
+}
+
+fn main() {
+    let program = intcode::read_program_from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/../day9/input9.txt"));
+    compare("boost test mode", &program, 1, 3235019597);
+    compare("boost sensor", &program, 2, 80274);
+}
@@ -0,0 +1,99 @@
+use super::{Context, Input, IntcodeError, Operands, Output};
+
+/// What `ADD`, `MUL` and `ARB` do when a result does not fit in a cell. The default wraps,
+/// in debug and release builds alike. Cells are always `i64`; there is no 128-bit or big
+/// integer mode, use `Checked` to find programs that need one.
+#[derive(std::fmt::Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArithmeticPolicy {
+    /// Fault with `IntcodeError::Overflow`.
+    Checked,
+    /// Two's complement wrap around.
+    #[default]
+    Wrapping,
+    /// Clamp to `i64::MIN` or `i64::MAX`.
+    Saturating,
+}
+
+impl ArithmeticPolicy {
+    /// Sum of `a` and `b`, `None` when it overflows under `Checked`.
+    pub fn add(self, a: i64, b: i64) -> Option<i64> {
+        match self {
+            ArithmeticPolicy::Checked => a.checked_add(b),
+            ArithmeticPolicy::Wrapping => Some(a.wrapping_add(b)),
+            ArithmeticPolicy::Saturating => Some(a.saturating_add(b)),
+        }
+    }
+
+    /// Product of `a` and `b`, `None` when it overflows under `Checked`.
+    pub fn multiply(self, a: i64, b: i64) -> Option<i64> {
+        match self {
+            ArithmeticPolicy::Checked => a.checked_mul(b),
+            ArithmeticPolicy::Wrapping => Some(a.wrapping_mul(b)),
+            ArithmeticPolicy::Saturating => Some(a.saturating_mul(b)),
+        }
+    }
+}
+
+impl Operands<'_> {
+    /// Adds under the arithmetic policy of the machine.
+    #[inline]
+    pub fn add(&self, a: i64, b: i64) -> Result<i64, IntcodeError> {
+        self.machine.arithmetic.add(a, b).ok_or_else(|| self.overflow())
+    }
+
+    /// Multiplies under the arithmetic policy of the machine.
+    #[inline]
+    pub fn multiply(&self, a: i64, b: i64) -> Result<i64, IntcodeError> {
+        self.machine.arithmetic.multiply(a, b).ok_or_else(|| self.overflow())
+    }
+
+    #[cold]
+    fn overflow(&self) -> IntcodeError {
+        IntcodeError::Overflow { ip: self.machine.ip, opcode: self.machine.opcode() }
+    }
+}
+
+impl<I: Input, O: Output> Context<I, O> {
+    pub fn set_arithmetic_policy(&mut self, policy: ArithmeticPolicy) {
+        self.machine.arithmetic = policy;
+    }
+
+    pub fn arithmetic_policy(&self) -> ArithmeticPolicy {
+        self.machine.arithmetic
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Status;
+
+    /// Outputs `value` times 2 plus 1.
+    fn run_with(policy: ArithmeticPolicy, value: i64) -> Result<Status, IntcodeError> {
+        let mut context = Context::with_memory(vec!(1002, 11, 2, 11, 1001, 11, 1, 11, 4, 11, 99, value));
+        context.set_arithmetic_policy(policy);
+        context.resume()
+    }
+
+    #[test]
+    fn test_policies() {
+        assert_eq!(run_with(ArithmeticPolicy::Checked, 20), Ok(Status::Output(41)));
+        assert_eq!(run_with(ArithmeticPolicy::Wrapping, i64::MAX / 2 + 1), Ok(Status::Output(i64::MIN + 1)));
+        assert_eq!(run_with(ArithmeticPolicy::Saturating, i64::MAX / 2 + 1), Ok(Status::Output(i64::MAX)));
+        assert_eq!(run_with(ArithmeticPolicy::Checked, i64::MAX / 2 + 1), Err(IntcodeError::Overflow { ip: 0, opcode: 1002 }));
+        assert_eq!(run_with(ArithmeticPolicy::Checked, i64::MAX / 2), Ok(Status::Output(i64::MAX)));
+    }
+
+    #[test]
+    fn test_relative_base() {
+        let mut context = Context::with_memory(vec!(109, i64::MAX, 109, 1, 99));
+        assert_eq!(context.arithmetic_policy(), ArithmeticPolicy::Wrapping);
+        context.set_arithmetic_policy(ArithmeticPolicy::Saturating);
+        assert_eq!(context.resume(), Ok(Status::Halted));
+        assert_eq!(context.relative_base(), i64::MAX);
+
+        let mut context = Context::with_memory(vec!(109, i64::MAX, 109, 1, 99));
+        context.set_arithmetic_policy(ArithmeticPolicy::Checked);
+        assert_eq!(context.resume(), Err(IntcodeError::Overflow { ip: 2, opcode: 109 }));
+    }
+}
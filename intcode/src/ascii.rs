@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+use std::io::BufRead;
+use std::sync::mpsc::{SendError, Sender};
+
+use super::{run, Context, Input, IntcodeError, Output};
+
+/// Output of an ASCII program: lines of text, without their newline, and values outside the
+/// ASCII range (such as a final answer) that are not text.
+#[derive(std::fmt::Debug, Clone, PartialEq, Eq)]
+pub enum Text {
+    Line(String),
+    Value(i64),
+}
+
+/// Character codes of `text`, with `\r\n` sent as a single `\n`. Characters outside ASCII
+/// are not rejected: they are sent as their Unicode code point, above 127, the range
+/// `AsciiDecoder` reads as values rather than text. The other adapters encode the same way.
+pub fn encode_ascii(text: &str) -> Vec<i64> {
+    text.replace("\r\n", "\n").chars().map(|c| c as i64).collect()
+}
+
+/// Turns output values into lines of text. Non-text values are returned as soon as they
+/// arrive; text before them on the same line stays buffered until the line ends.
+#[derive(std::fmt::Debug, Clone, Default)]
+pub struct AsciiDecoder {
+    partial: String,
+}
+
+impl AsciiDecoder {
+    pub fn new() -> AsciiDecoder {
+        AsciiDecoder::default()
+    }
+
+    pub fn push(&mut self, value: i64) -> Option<Text> {
+        match value {
+            10 => Some(Text::Line(std::mem::take(&mut self.partial))),
+            0..=127 => {
+                self.partial.push(value as u8 as char);
+                None
+            }
+            _ => Some(Text::Value(value)),
+        }
+    }
+
+    /// Text of the line that has not ended yet.
+    pub fn partial(&self) -> &str {
+        &self.partial
+    }
+
+    /// The unterminated last line, if any.
+    pub fn finish(self) -> Option<Text> {
+        Some(self.partial).filter(|partial| !partial.is_empty()).map(Text::Line)
+    }
+}
+
+/// Decodes a complete output, including an unterminated last line.
+pub fn decode_ascii(values: &[i64]) -> Vec<Text> {
+    let mut decoder = AsciiDecoder::new();
+    let mut text: Vec<Text> = values.iter().filter_map(|&value| decoder.push(value)).collect();
+    text.extend(decoder.finish());
+    text
+}
+
+/// Runs an ASCII program to completion on `input` and decodes its output.
+pub fn run_ascii(program: &[i64], input: &str) -> Result<Vec<Text>, IntcodeError> {
+    let mut context = Context::new(program.to_vec(), VecDeque::from(encode_ascii(input)), AsciiOutput::new());
+    run(&mut context)?;
+    Ok(context.into_output().finish())
+}
+
+/// Output sink collecting decoded text.
+#[derive(std::fmt::Debug, Clone, Default)]
+pub struct AsciiOutput {
+    decoder: AsciiDecoder,
+    text: Vec<Text>,
+}
+
+impl AsciiOutput {
+    pub fn new() -> AsciiOutput {
+        AsciiOutput::default()
+    }
+
+    /// Complete lines and non-text values received so far.
+    pub fn text(&self) -> &[Text] {
+        &self.text
+    }
+
+    pub fn partial(&self) -> &str {
+        self.decoder.partial()
+    }
+
+    /// Everything received, including an unterminated last line.
+    pub fn finish(mut self) -> Vec<Text> {
+        self.text.extend(self.decoder.finish());
+        self.text
+    }
+}
+
+impl Output for AsciiOutput {
+    fn write(&mut self, value: i64) -> std::io::Result<()> {
+        self.text.extend(self.decoder.push(value));
+        Ok(())
+    }
+}
+
+/// Input source reading text, one character code per value, with `\r\n` read as `\n`. Input
+/// ends at the end of the reader or at the first read error.
+pub struct AsciiInput<R: BufRead> {
+    reader: R,
+    pending: std::vec::IntoIter<i64>,
+}
+
+impl<R: BufRead> AsciiInput<R> {
+    pub fn new(reader: R) -> AsciiInput<R> {
+        AsciiInput { reader, pending: Vec::new().into_iter() }
+    }
+}
+
+impl<R: BufRead> Input for AsciiInput<R> {
+    fn read(&mut self) -> Option<i64> {
+        loop {
+            if let Some(value) = self.pending.next() {
+                return Some(value);
+            }
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            self.pending = encode_ascii(&line).into_iter();
+        }
+    }
+}
+
+/// Sends the character codes of `text` over a channel feeding a `Context`.
+pub fn send_text(sender: &Sender<i64>, text: &str) -> Result<(), SendError<i64>> {
+    encode_ascii(text).into_iter().try_for_each(|value| sender.send(value))
+}
+
+impl<I: Input, O: Output> Context<I, O> {
+    /// Queues the character codes of `text` as input.
+    pub fn push_text(&mut self, text: &str) {
+        for value in encode_ascii(text) {
+            self.push_input(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Status;
+    use std::sync::mpsc::channel;
+
+    // Echoes its input until it reads a 0, then outputs 1000.
+    const ECHO: [i64; 14] = [3, 13, 1006, 13, 10, 4, 13, 1105, 1, 0, 104, 1000, 99, 0];
+
+    #[test]
+    fn test_run_ascii() {
+        assert_eq!(run_ascii(&ECHO, "ab\r\ncd\n\u{0}"), Ok(vec!(
+            Text::Line("ab".to_string()),
+            Text::Line("cd".to_string()),
+            Text::Value(1000))));
+        assert_eq!(run_ascii(&ECHO, "é"), Err(IntcodeError::InputClosed { ip: 0, opcode: 3 }));
+        assert_eq!(encode_ascii("é"), vec!(233));
+        assert_eq!(decode_ascii(&[104, 105, -1, 10, 33]), vec!(
+            Text::Value(-1),
+            Text::Line("hi".to_string()),
+            Text::Line("!".to_string())));
+    }
+
+    #[test]
+    fn test_context_adapters() {
+        let (sender, receiver) = channel();
+        send_text(&sender, "x\n\u{0}").unwrap();
+        let mut context = Context::new(ECHO.to_vec(), receiver, AsciiOutput::new());
+        run(&mut context).unwrap();
+        assert_eq!(context.into_output().finish(), vec!(Text::Line("x".to_string()), Text::Value(1000)));
+
+        let mut context = Context::new(ECHO.to_vec(), AsciiInput::new("yz".as_bytes()), AsciiOutput::new());
+        context.push_text("w");
+        assert_eq!(context.resume(), Ok(Status::Output(119)));
+        context.output_mut().write(119).unwrap();
+        assert_eq!(context.resume(), Ok(Status::Output(121)));
+        assert_eq!(context.output().partial(), "w");
+    }
+}
@@ -0,0 +1,467 @@
+//! Assembler for the syntax produced by `listing`:
+//!
+//! ```text
+//! ; comments run to the end of the line
+//! count = 3                ; constant
+//! start:  IN   value       ; label and instruction
+//!         MUL  value, #count + 1, @-1
+//!         JNZ  #1, #start
+//! value:  DATA 0, -1, end - start
+//!         TEXT "hi\n"      ; one cell per character
+//! end:    HLT
+//! ```
+//!
+//! Operands are expressions over numbers, labels and constants with `+`, `-`, `*` and
+//! parentheses, prefixed by `#` for immediate mode or `@` for relative mode. A numeric
+//! label such as `12:` asserts the address of the line, so listings assemble unchanged.
+
+use std::collections::HashMap;
+
+use super::{INSTRUCTIONS, Instruction, InstructionSet, Mode};
+
+#[derive(std::fmt::Debug, PartialEq, Eq, Clone)]
+pub enum AssemblyError {
+    Syntax { line: usize, message: String },
+    UnknownMnemonic { line: usize, mnemonic: String },
+    OperandCount { line: usize, mnemonic: String, expected: usize, found: usize },
+    DuplicateSymbol { line: usize, name: String },
+    UndefinedSymbol { line: usize, name: String },
+    RecursiveConstant { line: usize, name: String },
+    AddressMismatch { line: usize, expected: usize, actual: usize },
+    /// An expression whose value does not fit in a cell.
+    Overflow { line: usize },
+}
+
+impl std::fmt::Display for AssemblyError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            AssemblyError::Syntax { line, message } =>
+                write!(fmt, "line {}: {}", line, message),
+            AssemblyError::UnknownMnemonic { line, mnemonic } =>
+                write!(fmt, "line {}: unknown mnemonic {}", line, mnemonic),
+            AssemblyError::OperandCount { line, mnemonic, expected, found } =>
+                write!(fmt, "line {}: {} takes {} operands, found {}", line, mnemonic, expected, found),
+            AssemblyError::DuplicateSymbol { line, name } =>
+                write!(fmt, "line {}: symbol {} is already defined", line, name),
+            AssemblyError::UndefinedSymbol { line, name } =>
+                write!(fmt, "line {}: undefined symbol {}", line, name),
+            AssemblyError::RecursiveConstant { line, name } =>
+                write!(fmt, "line {}: constant {} is defined in terms of itself", line, name),
+            AssemblyError::AddressMismatch { line, expected, actual } =>
+                write!(fmt, "line {}: expected address {}, actual address is {}", line, expected, actual),
+            AssemblyError::Overflow { line } =>
+                write!(fmt, "line {}: value out of range", line),
+        }
+    }
+}
+
+impl std::error::Error for AssemblyError {}
+
+#[derive(std::fmt::Debug, Clone)]
+enum Expression {
+    Number(i64),
+    Symbol(String),
+    Negate(Box<Expression>),
+    Add(Box<Expression>, Box<Expression>),
+    Subtract(Box<Expression>, Box<Expression>),
+    Multiply(Box<Expression>, Box<Expression>),
+}
+
+#[derive(std::fmt::Debug)]
+enum Statement {
+    Instruction { instruction: Instruction, operands: Vec<(Mode, Expression)> },
+    Data(Vec<Expression>),
+}
+
+#[derive(std::fmt::Debug)]
+enum Symbol {
+    Address(usize),
+    Constant(Expression, usize),
+}
+
+/// Assembles source text into a program ready for `Context::new`.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AssemblyError> {
+    assemble_table(source, &INSTRUCTIONS)
+}
+
+/// Like `assemble`, with the mnemonics of `instructions`. `DATA` and `TEXT` are always read
+/// as directives, and mnemonics that are not identifiers cannot be written.
+pub fn assemble_with(source: &str, instructions: &InstructionSet) -> Result<Vec<i64>, AssemblyError> {
+    assemble_table(source, &instructions.instructions)
+}
+
+fn assemble_table(source: &str, instructions: &[Option<Instruction>; 100]) -> Result<Vec<i64>, AssemblyError> {
+    let mut symbols: HashMap<String, Symbol> = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = 0;
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let mut parser = Parser { text: strip_comment(text), position: 0, line };
+
+        while let Some(label) = parser.label()? {
+            match label.parse::<usize>() {
+                Ok(expected) if expected != address =>
+                    return Err(AssemblyError::AddressMismatch { line, expected, actual: address }),
+                Ok(_) => {}
+                Err(_) => define(&mut symbols, label, Symbol::Address(address), line)?,
+            }
+        }
+        if parser.at_end() {
+            continue;
+        }
+        let name = parser.identifier()
+            .ok_or_else(|| parser.error("expected a mnemonic, directive or constant definition"))?;
+        if parser.eat('=') {
+            let expression = parser.expression()?;
+            parser.expect_end()?;
+            define(&mut symbols, name, Symbol::Constant(expression, line), line)?;
+            continue;
+        }
+        let statement = parser.statement(&name, instructions)?;
+        address += match &statement {
+            Statement::Instruction { operands, .. } => operands.len() + 1,
+            Statement::Data(values) => values.len(),
+        };
+        statements.push((line, statement));
+    }
+
+    let mut program = Vec::with_capacity(address);
+    for (line, statement) in statements {
+        match statement {
+            Statement::Instruction { instruction, operands } => {
+                let mut opcode = instruction.opcode as i64;
+                let mut factor = 100;
+                for (mode, _) in &operands {
+                    opcode += mode.digit() * factor;
+                    factor *= 10;
+                }
+                program.push(opcode);
+                for (_, expression) in &operands {
+                    program.push(evaluate(expression, &symbols, line, &mut Vec::new())?);
+                }
+            }
+            Statement::Data(values) => {
+                for expression in &values {
+                    program.push(evaluate(expression, &symbols, line, &mut Vec::new())?);
+                }
+            }
+        }
+    }
+    Ok(program)
+}
+
+fn define(symbols: &mut HashMap<String, Symbol>, name: String, symbol: Symbol, line: usize) -> Result<(), AssemblyError> {
+    if symbols.contains_key(&name) {
+        return Err(AssemblyError::DuplicateSymbol { line, name });
+    }
+    symbols.insert(name, symbol);
+    Ok(())
+}
+
+fn evaluate(expression: &Expression, symbols: &HashMap<String, Symbol>, line: usize, visiting: &mut Vec<String>) -> Result<i64, AssemblyError> {
+    Ok(match expression {
+        Expression::Number(value) => *value,
+        Expression::Symbol(name) => match symbols.get(name) {
+            Some(Symbol::Address(address)) => *address as i64,
+            Some(Symbol::Constant(expression, defined)) => {
+                if visiting.contains(name) {
+                    return Err(AssemblyError::RecursiveConstant { line: *defined, name: name.clone() });
+                }
+                visiting.push(name.clone());
+                let value = evaluate(expression, symbols, *defined, visiting)?;
+                visiting.pop();
+                value
+            }
+            None => return Err(AssemblyError::UndefinedSymbol { line, name: name.clone() }),
+        },
+        Expression::Negate(operand) => evaluate(operand, symbols, line, visiting)?.checked_neg()
+            .ok_or(AssemblyError::Overflow { line })?,
+        Expression::Add(left, right) => evaluate(left, symbols, line, visiting)?.checked_add(evaluate(right, symbols, line, visiting)?)
+            .ok_or(AssemblyError::Overflow { line })?,
+        Expression::Subtract(left, right) => evaluate(left, symbols, line, visiting)?.checked_sub(evaluate(right, symbols, line, visiting)?)
+            .ok_or(AssemblyError::Overflow { line })?,
+        Expression::Multiply(left, right) => evaluate(left, symbols, line, visiting)?.checked_mul(evaluate(right, symbols, line, visiting)?)
+            .ok_or(AssemblyError::Overflow { line })?,
+    })
+}
+
+fn strip_comment(text: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &text[..i],
+            _ => {}
+        }
+    }
+    text
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> AssemblyError {
+        AssemblyError::Syntax { line: self.line, message: format!("{} at column {}", message, self.position + 1) }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.position += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.peek().is_none()
+    }
+
+    fn expect_end(&mut self) -> Result<(), AssemblyError> {
+        if self.at_end() { Ok(()) } else { Err(self.error("unexpected text")) }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let length = rest.find(|c: char| !predicate(c)).unwrap_or(rest.len());
+        self.position += length;
+        &rest[..length]
+    }
+
+    fn identifier(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.' =>
+                Some(self.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.').to_string()),
+            _ => None,
+        }
+    }
+
+    /// Consumes `name:` or `number:` at the current position, if present.
+    fn label(&mut self) -> Result<Option<String>, AssemblyError> {
+        let start = self.position;
+        self.skip_whitespace();
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+        if !name.is_empty() && self.rest().starts_with(':') {
+            self.position += 1;
+            if name.starts_with(|c: char| c.is_ascii_digit()) && name.parse::<usize>().is_err() {
+                return Err(self.error("invalid label"));
+            }
+            return Ok(Some(name.to_string()));
+        }
+        self.position = start;
+        Ok(None)
+    }
+
+    fn statement(&mut self, name: &str, instructions: &[Option<Instruction>; 100]) -> Result<Statement, AssemblyError> {
+        let mnemonic = name.to_ascii_uppercase();
+        match mnemonic.as_str() {
+            "DATA" => Ok(Statement::Data(self.list(|parser| parser.expression())?)),
+            "TEXT" => {
+                let text = self.string()?;
+                self.expect_end()?;
+                Ok(Statement::Data(text.chars().map(|c| Expression::Number(c as i64)).collect()))
+            }
+            _ => {
+                let &instruction = instructions.iter().flatten()
+                    .find(|instruction| instruction.mnemonic.eq_ignore_ascii_case(&mnemonic))
+                    .ok_or(AssemblyError::UnknownMnemonic { line: self.line, mnemonic: name.to_string() })?;
+                let operands = self.list(|parser| parser.operand())?;
+                if operands.len() != instruction.operand_count {
+                    return Err(AssemblyError::OperandCount {
+                        line: self.line,
+                        mnemonic: mnemonic.clone(),
+                        expected: instruction.operand_count,
+                        found: operands.len(),
+                    });
+                }
+                Ok(Statement::Instruction { instruction, operands })
+            }
+        }
+    }
+
+    fn list<T>(&mut self, item: impl Fn(&mut Self) -> Result<T, AssemblyError>) -> Result<Vec<T>, AssemblyError> {
+        let mut items = Vec::new();
+        if self.at_end() {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if !self.eat(',') {
+                self.expect_end()?;
+                return Ok(items);
+            }
+        }
+    }
+
+    fn operand(&mut self) -> Result<(Mode, Expression), AssemblyError> {
+        let mode = if self.eat('#') {
+            Mode::Immediate
+        } else if self.eat('@') {
+            Mode::Relative
+        } else {
+            Mode::Position
+        };
+        Ok((mode, self.expression()?))
+    }
+
+    fn expression(&mut self) -> Result<Expression, AssemblyError> {
+        let mut left = self.term()?;
+        loop {
+            if self.eat('+') {
+                left = Expression::Add(Box::new(left), Box::new(self.term()?));
+            } else if self.eat('-') {
+                left = Expression::Subtract(Box::new(left), Box::new(self.term()?));
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Expression, AssemblyError> {
+        let mut left = self.factor()?;
+        while self.eat('*') {
+            left = Expression::Multiply(Box::new(left), Box::new(self.factor()?));
+        }
+        Ok(left)
+    }
+
+    fn factor(&mut self) -> Result<Expression, AssemblyError> {
+        if self.eat('-') {
+            // A negative literal is parsed whole, so that `i64::MIN` can be written.
+            if self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                let digits = self.take_while(|c| c.is_ascii_digit());
+                return format!("-{}", digits).parse().map(Expression::Number).map_err(|_| AssemblyError::Overflow { line: self.line });
+            }
+            return Ok(Expression::Negate(Box::new(self.factor()?)));
+        }
+        if self.eat('(') {
+            let expression = self.expression()?;
+            if !self.eat(')') {
+                return Err(self.error("expected )"));
+            }
+            return Ok(expression);
+        }
+        if let Some(name) = self.identifier() {
+            return Ok(Expression::Symbol(name));
+        }
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        if digits.is_empty() {
+            return Err(self.error("expected a number, symbol or ("));
+        }
+        digits.parse().map(Expression::Number).map_err(|_| AssemblyError::Overflow { line: self.line })
+    }
+
+    fn string(&mut self) -> Result<String, AssemblyError> {
+        if !self.eat('"') {
+            return Err(self.error("expected a string"));
+        }
+        let mut text = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.position += i + 1;
+                    return Ok(text);
+                }
+                '\\' => text.push(match chars.next() {
+                    Some((_, 'n')) => '\n',
+                    Some((_, 't')) => '\t',
+                    Some((_, c)) => c,
+                    None => break,
+                }),
+                c => text.push(c),
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{listing, run_input_output};
+
+    #[test]
+    fn test_assemble_modes() {
+        assert_eq!(assemble("MUL 16, #10, 16\nADD @-1, #-5, @2\nHLT"), Ok(vec!(1002, 16, 10, 16, 21201, -1, -5, 2, 99)));
+    }
+
+    #[test]
+    fn test_assemble_labels_constants_and_data() {
+        let source = "
+            offset = 10 * 2 - (1 + 1)   ; 18
+            start:  IN   value
+                    ADD  value, #offset, value
+                    OUT  value
+                    JZ   #0, #end
+            value:  DATA 0, end - start
+            end:    hlt";
+        assert_eq!(assemble(source), Ok(vec!(3, 11, 1001, 11, 18, 11, 4, 11, 1106, 0, 13, 0, 13, 99)));
+        assert_eq!(run_input_output(&assemble(source).unwrap(), &[24]), vec!(42));
+    }
+
+    #[test]
+    fn test_assemble_text() {
+        assert_eq!(assemble("TEXT \"a;\\n\" ; comment"), Ok(vec!(97, 59, 10)));
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        assert_eq!(assemble("NOP"), Err(AssemblyError::UnknownMnemonic { line: 1, mnemonic: "NOP".to_string() }));
+        assert_eq!(assemble("\nADD 1, 2"), Err(AssemblyError::OperandCount { line: 2, mnemonic: "ADD".to_string(), expected: 3, found: 2 }));
+        assert_eq!(assemble("OUT missing"), Err(AssemblyError::UndefinedSymbol { line: 1, name: "missing".to_string() }));
+        assert_eq!(assemble("a: HLT\na: HLT"), Err(AssemblyError::DuplicateSymbol { line: 2, name: "a".to_string() }));
+        assert_eq!(assemble("a = b\nb = a\nOUT a"), Err(AssemblyError::RecursiveConstant { line: 1, name: "a".to_string() }));
+        assert_eq!(assemble("HLT\n0: HLT"), Err(AssemblyError::AddressMismatch { line: 2, expected: 0, actual: 1 }));
+        assert_eq!(assemble("DATA 9223372036854775807 + 1"), Err(AssemblyError::Overflow { line: 1 }));
+        assert_eq!(assemble("\nDATA -(-9223372036854775808)"), Err(AssemblyError::Overflow { line: 2 }));
+        assert_eq!(assemble("DATA 9223372036854775808"), Err(AssemblyError::Overflow { line: 1 }));
+    }
+
+    #[test]
+    fn test_listing_round_trip() {
+        let program = vec!(
+            3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+            1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+            999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99,
+            109,-3,21201,-1,4,2,204,7,10099,-42,i64::MIN,i64::MAX);
+        assert_eq!(assemble(&listing(&program)), Ok(program.clone()));
+        assert_eq!(listing(&assemble(&listing(&program)).unwrap()), listing(&program));
+    }
+
+    #[test]
+    fn test_custom_instruction_set() {
+        fn nop(_: &mut crate::Operands) -> Result<crate::Flow, crate::IntcodeError> {
+            Ok(crate::Flow::Next)
+        }
+        let mut instructions = InstructionSet::standard();
+        instructions.define(Instruction { opcode: 42, mnemonic: "SKIP", operand_count: 1, write_operand: None, implementation: nop }).unwrap();
+        let program = assemble_with("skip #7\nHLT", &instructions).unwrap();
+        assert_eq!(program, vec!(142, 7, 99));
+        assert_eq!(crate::listing_with(&program, &instructions), "    0: SKIP #7\n    2: HLT\n");
+        assert_eq!(crate::listing(&program), "    0: DATA 142\n    1: DATA 7\n    2: HLT\n");
+        assert_eq!(assemble("SKIP #7"), Err(AssemblyError::UnknownMnemonic { line: 1, mnemonic: "SKIP".to_string() }));
+    }
+}
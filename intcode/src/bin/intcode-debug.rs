@@ -0,0 +1,33 @@
+use std::process;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        eprintln!("usage: {} <program> [input...]", args[0]);
+        process::exit(2);
+    }
+    let memory = match intcode::load_program_file(&args[1]) {
+        Ok(memory) => memory,
+        Err(error) => {
+            eprintln!("{}: {}", args[1], error);
+            process::exit(1);
+        }
+    };
+    let mut context = intcode::Context::new(memory, (), std::io::stdout());
+    for value in &args[2..] {
+        match value.parse() {
+            Ok(value) => context.push_input(value),
+            Err(_) => {
+                eprintln!("invalid input value {}", value);
+                process::exit(2);
+            }
+        }
+    }
+
+    let mut debugger = intcode::Debugger::new(context);
+    let stdin = std::io::stdin();
+    if let Err(error) = intcode::repl(&mut debugger, stdin.lock(), std::io::stdout()) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
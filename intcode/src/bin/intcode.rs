@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+use std::io::{IsTerminal, Write};
+use std::process;
+
+use intcode::{Context, Input, IntcodeError, Status};
+
+const USAGE: &str = "\
+usage: intcode [options] <program>
+
+The program is a text file of comma separated values or a file written by `write_binary`.
+
+  --ascii          exchange text: input is sent as character codes, output printed as text
+  --input <value>  preload input before reading the terminal; comma separated numbers, or a
+                   line of text in ASCII mode; may be repeated
+  --echo           print numeric outputs as they are produced instead of all at once at the end,
+                   or before each prompt when reading from a terminal; ASCII output is always
+                   printed as it arrives
+  --summary        print instruction, input and output counts on halt";
+
+struct Options {
+    ascii: bool,
+    inputs: Vec<String>,
+    echo: bool,
+    summary: bool,
+    program: String,
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn parse_options(args: &[String]) -> Options {
+    let mut options = Options { ascii: false, inputs: Vec::new(), echo: false, summary: false, program: String::new() };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ascii" => options.ascii = true,
+            "--echo" => options.echo = true,
+            "--summary" => options.summary = true,
+            "--input" => match args.next() {
+                Some(value) => options.inputs.push(value.clone()),
+                None => usage_error("--input needs a value"),
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            option if option.starts_with("--") => usage_error(&format!("unknown option {}", option)),
+            program if options.program.is_empty() => options.program = program.to_string(),
+            extra => usage_error(&format!("unexpected argument {}", extra)),
+        }
+    }
+    if options.program.is_empty() {
+        usage_error("no program given");
+    }
+    options
+}
+
+fn preload(options: &Options) -> VecDeque<i64> {
+    let mut preloaded = VecDeque::new();
+    for input in &options.inputs {
+        if options.ascii {
+            preloaded.extend(intcode::encode_ascii(input));
+            preloaded.push_back(10);
+            continue;
+        }
+        for value in input.split(',').map(str::trim).filter(|value| !value.is_empty()) {
+            match value.parse() {
+                Ok(value) => preloaded.push_back(value),
+                Err(_) => usage_error(&format!("invalid input value {}", value)),
+            }
+        }
+    }
+    preloaded
+}
+
+#[derive(Default)]
+struct Session {
+    inputs: usize,
+    outputs: usize,
+    /// Numeric outputs not echoed, printed before the next prompt or on halt.
+    held: Vec<i64>,
+}
+
+impl Session {
+    fn print_held(&mut self) {
+        if !self.held.is_empty() {
+            let outputs: Vec<String> = self.held.drain(..).map(|value| value.to_string()).collect();
+            println!("{}", outputs.join(","));
+        }
+    }
+}
+
+/// Runs until halt, reading input from the terminal once the preloaded input runs out.
+fn run(context: &mut Context, options: &Options, preloaded: &mut VecDeque<i64>, session: &mut Session) -> Result<(), IntcodeError> {
+    let stdin = std::io::stdin();
+    let interactive = stdin.is_terminal();
+    // Holds the lock on stdin, so numeric mode reads through `stdin` instead.
+    let mut text_input = if options.ascii { Some(intcode::AsciiInput::new(std::io::stdin().lock())) } else { None };
+    let mut stdout = std::io::stdout();
+    let mut line_start = true;
+    loop {
+        let status = context.resume()?;
+        if let Status::Output(_) = status {
+            session.outputs += 1;
+        }
+        match status {
+            Status::Output(value) if options.ascii => {
+                if (0..=127).contains(&value) {
+                    let _ = write!(stdout, "{}", value as u8 as char);
+                    line_start = value == 10;
+                } else {
+                    let _ = writeln!(stdout, "{}{}", if line_start { "" } else { "\n" }, value);
+                    line_start = true;
+                }
+            }
+            Status::Output(value) => {
+                if options.echo {
+                    let _ = writeln!(stdout, "{}", value);
+                } else {
+                    session.held.push(value);
+                }
+            }
+            Status::NeedsInput => {
+                let value = match preloaded.pop_front() {
+                    Some(value) => Some(value),
+                    None => {
+                        if interactive {
+                            session.print_held();
+                        }
+                        let _ = stdout.flush();
+                        match &mut text_input {
+                            Some(text_input) => text_input.read(),
+                            None => intcode::read_integer(&mut stdin.lock(), &mut std::io::stderr(), if interactive { "> " } else { "" }),
+                        }
+                    }
+                };
+                match value {
+                    Some(value) => context.push_input(value),
+                    None => return Err(IntcodeError::InputClosed { ip: context.ip(), opcode: context.read(context.ip()) }),
+                }
+                session.inputs += 1;
+            }
+            Status::Halted => break,
+        }
+    }
+    let _ = stdout.flush();
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let options = parse_options(&args[1..]);
+    let mut context = match intcode::load_program_file(&options.program) {
+        Ok(memory) => Context::with_memory(memory),
+        Err(error) => {
+            eprintln!("{}: {}", options.program, error);
+            process::exit(1);
+        }
+    };
+    let mut preloaded = preload(&options);
+    let preloaded_count = preloaded.len();
+    let mut session = Session::default();
+
+    let result = run(&mut context, &options, &mut preloaded, &mut session);
+    session.print_held();
+    if options.summary {
+        eprintln!("{} after {} instructions at ip {}: {} inputs ({} preloaded, {} left unused), {} outputs",
+                  if result.is_ok() { "halted" } else { "stopped" },
+                  context.executed(), context.ip(), session.inputs, preloaded_count, preloaded.len(), session.outputs);
+    }
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
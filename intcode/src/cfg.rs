@@ -0,0 +1,300 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+use super::disassembler::decode_table;
+use super::{Instruction, InstructionSet, Line, Mode, TraceRecord, Tracer, INSTRUCTIONS};
+
+const JUMP_NOT_ZERO: i64 = 5;
+const JUMP_ZERO: i64 = 6;
+const HALT: i64 = 99;
+
+#[derive(std::fmt::Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// A jump with an immediate target.
+    Taken,
+    FallThrough,
+}
+
+#[derive(std::fmt::Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    /// Start address of the source block.
+    pub from: usize,
+    /// Start address of the target block.
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// A run of instructions that is only entered at its first instruction and only left after
+/// its last one.
+#[derive(std::fmt::Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub lines: Vec<Line>,
+    /// Ends in a jump whose target is not an immediate address inside the program, such as
+    /// a return through a relative mode parameter.
+    pub computed_jump: bool,
+    /// Runs into a cell that does not decode or into the end of the program.
+    pub invalid: bool,
+}
+
+impl BasicBlock {
+    /// Address of the last instruction.
+    pub fn last(&self) -> usize {
+        self.lines.last().map_or(self.start, |line| line.address())
+    }
+}
+
+/// Blocks and edges reachable from address 0, recovered without running the program. Code
+/// that is only reached through computed jumps, or that the program writes itself, is missing.
+#[derive(std::fmt::Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    pub blocks: BTreeMap<usize, BasicBlock>,
+    pub edges: Vec<Edge>,
+}
+
+/// How a decoded jump continues: `taken` is the target when it is known, `computed` tells an
+/// unknown target exists, `falls_through` whether the jump can be not taken.
+struct Jump {
+    taken: Option<usize>,
+    computed: bool,
+    falls_through: bool,
+}
+
+fn jump(program: &[i64], line: &Line) -> Option<Jump> {
+    let (opcode, operands) = match line {
+        Line::Instruction { opcode, operands, .. } if matches!(opcode % 100, JUMP_NOT_ZERO | JUMP_ZERO) => (opcode % 100, operands),
+        _ => return None,
+    };
+    let (condition, target) = (operands[0], operands[1]);
+    let (may_jump, may_fall_through) = match condition.mode {
+        Mode::Immediate => {
+            let jumps = (condition.value != 0) == (opcode == JUMP_NOT_ZERO);
+            (jumps, !jumps)
+        }
+        _ => (true, true),
+    };
+    let known = target.mode == Mode::Immediate && target.value >= 0 && (target.value as usize) < program.len();
+    Some(Jump {
+        taken: if may_jump && known { Some(target.value as usize) } else { None },
+        computed: may_jump && !known,
+        falls_through: may_fall_through,
+    })
+}
+
+/// Recovers the control flow graph of `program` by following both sides of every
+/// conditional jump from address 0. Jumps with an immediate condition only get the edge
+/// they can take.
+pub fn control_flow_graph(program: &[i64]) -> ControlFlowGraph {
+    graph(&INSTRUCTIONS, program)
+}
+
+/// Like `control_flow_graph`, decoding with the opcodes of `instructions`. Jumps are still
+/// recognised by opcodes 5 and 6 and halts by opcode 99; every other instruction falls through.
+pub fn control_flow_graph_with(program: &[i64], instructions: &InstructionSet) -> ControlFlowGraph {
+    graph(&instructions.instructions, program)
+}
+
+fn graph(table: &[Option<Instruction>; 100], program: &[i64]) -> ControlFlowGraph {
+    let mut instructions: BTreeMap<usize, Line> = BTreeMap::new();
+    let mut leaders: BTreeSet<usize> = BTreeSet::new();
+    let mut pending = vec!(0);
+    leaders.insert(0);
+    while let Some(address) = pending.pop() {
+        if address >= program.len() || instructions.contains_key(&address) {
+            continue;
+        }
+        let line = decode_table(table, program, address);
+        let next = address + line.size();
+        match (&line, jump(program, &line)) {
+            (_, Some(jump)) => {
+                for target in jump.taken.into_iter().chain(Some(next).filter(|_| jump.falls_through)) {
+                    leaders.insert(target);
+                    pending.push(target);
+                }
+            }
+            (Line::Instruction { opcode, .. }, None) if opcode % 100 != HALT => pending.push(next),
+            _ => {}
+        }
+        instructions.insert(address, line);
+    }
+
+    let mut blocks = BTreeMap::new();
+    let mut edges = Vec::new();
+    for &start in &leaders {
+        let mut block = BasicBlock { start, lines: Vec::new(), computed_jump: false, invalid: false };
+        let mut address = start;
+        loop {
+            let line = match instructions.get(&address) {
+                Some(line) => line.clone(),
+                None => {
+                    block.invalid = true;
+                    break;
+                }
+            };
+            let next = address + line.size();
+            let jump = jump(program, &line);
+            let stop = match &line {
+                Line::Data { .. } => {
+                    block.invalid = true;
+                    true
+                }
+                Line::Instruction { opcode, .. } => opcode % 100 == HALT || jump.is_some(),
+            };
+            block.lines.push(line);
+            if let Some(jump) = jump {
+                block.computed_jump = jump.computed;
+                if let Some(target) = jump.taken {
+                    edges.push(Edge { from: start, to: target, kind: EdgeKind::Taken });
+                }
+                if jump.falls_through {
+                    edges.push(Edge { from: start, to: next, kind: EdgeKind::FallThrough });
+                }
+            }
+            if stop {
+                break;
+            }
+            if leaders.contains(&next) {
+                edges.push(Edge { from: start, to: next, kind: EdgeKind::FallThrough });
+                break;
+            }
+            address = next;
+        }
+        if block.lines.is_empty() {
+            continue;
+        }
+        blocks.insert(start, block);
+    }
+    edges.retain(|edge| blocks.contains_key(&edge.to));
+    ControlFlowGraph { blocks, edges }
+}
+
+impl ControlFlowGraph {
+    /// Graphviz DOT form. Fall-through edges are dashed, blocks ending in a computed jump
+    /// are red and invalid blocks orange.
+    pub fn to_dot(&self) -> String {
+        self.dot(None)
+    }
+
+    /// Like `to_dot`, labelling every edge with the number of times it was followed.
+    pub fn to_dot_with_counts(&self, counts: &EdgeCounts) -> String {
+        self.dot(Some(counts))
+    }
+
+    fn dot(&self, counts: Option<&EdgeCounts>) -> String {
+        let mut dot = String::from("digraph intcode {\n    node [shape=box, fontname=\"monospace\"];\n");
+        for block in self.blocks.values() {
+            let mut label: String = block.lines.iter().map(|line| format!("{:>5}: {}\\l", line.address(), dot_escape(&line.to_string()))).collect();
+            let mut attributes = String::new();
+            if block.computed_jump {
+                label.push_str("computed jump\\l");
+                attributes.push_str(", color=red");
+            }
+            if block.invalid {
+                label.push_str("invalid\\l");
+                attributes.push_str(", color=orange");
+            }
+            let _ = writeln!(dot, "    b{} [label=\"{}\"{}];", block.start, label, attributes);
+        }
+        for edge in &self.edges {
+            let mut attributes = Vec::new();
+            if edge.kind == EdgeKind::FallThrough {
+                attributes.push("style=dashed".to_string());
+            }
+            if let Some(counts) = counts {
+                attributes.push(format!("label=\"{}\"", counts.count(self.blocks[&edge.from].last(), edge.to)));
+            }
+            let attributes = if attributes.is_empty() { String::new() } else { format!(" [{}]", attributes.join(", ")) };
+            let _ = writeln!(dot, "    b{} -> b{}{};", edge.from, edge.to, attributes);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Counts transitions between consecutively executed instructions, for the edge labels of
+/// `ControlFlowGraph::to_dot_with_counts`.
+#[derive(std::fmt::Debug, Clone, Default)]
+pub struct EdgeCounts {
+    transitions: HashMap<(usize, usize), u64>,
+    last: Option<usize>,
+}
+
+impl Tracer for EdgeCounts {
+    fn trace(&mut self, record: &TraceRecord) {
+        if let Some(last) = self.last {
+            *self.transitions.entry((last, record.ip)).or_insert(0) += 1;
+        }
+        self.last = Some(record.ip);
+    }
+}
+
+impl EdgeCounts {
+    pub fn new() -> EdgeCounts {
+        EdgeCounts::default()
+    }
+
+    /// Number of times the instruction at `to` was executed right after the one at `from`.
+    pub fn count(&self, from: usize, to: usize) -> u64 {
+        self.transitions.get(&(from, to)).copied().unwrap_or(0)
+    }
+}
+
+/// `text` with `"` and `\` escaped for a quoted DOT string.
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run_traced, Context};
+
+    // 0: ADD 16, #-1, 16   4: JNZ 16, #0   7: JZ 16, #11   10: DATA 42
+    // 11: ARB #1   13: JNZ #1, @0   16: counter
+    const PROGRAM: [i64; 17] = [1001, 16, -1, 16, 1005, 16, 0, 1006, 16, 11, 42, 109, 1, 2105, 1, 0, 2];
+
+    #[test]
+    fn test_blocks_and_edges() {
+        let graph = control_flow_graph(&PROGRAM);
+        assert_eq!(graph.blocks.keys().copied().collect::<Vec<usize>>(), vec!(0, 7, 10, 11));
+        assert_eq!(graph.edges, vec!(
+            Edge { from: 0, to: 0, kind: EdgeKind::Taken },
+            Edge { from: 0, to: 7, kind: EdgeKind::FallThrough },
+            Edge { from: 7, to: 11, kind: EdgeKind::Taken },
+            Edge { from: 7, to: 10, kind: EdgeKind::FallThrough }));
+        assert_eq!(graph.blocks[&0].lines.len(), 2);
+        assert!(graph.blocks[&10].invalid);
+        assert!(graph.blocks[&11].computed_jump);
+    }
+
+    #[test]
+    fn test_dot_with_counts() {
+        let program = vec!(1001, 9, -1, 9, 1005, 9, 0, 99, 0, 3);
+        let mut counts = EdgeCounts::new();
+        run_traced(&mut Context::with_memory(program.clone()), &mut counts).unwrap();
+        let graph = control_flow_graph(&program);
+        assert_eq!(graph.to_dot_with_counts(&counts), "\
+digraph intcode {
+    node [shape=box, fontname=\"monospace\"];
+    b0 [label=\"    0: ADD  9, #-1, 9\\l    4: JNZ  9, #0\\l\"];
+    b7 [label=\"    7: HLT\\l\"];
+    b0 -> b0 [label=\"2\"];
+    b0 -> b7 [style=dashed, label=\"1\"];
+}
+");
+        let graph = control_flow_graph(&[2106, 0, 0]);
+        assert_eq!(graph.to_dot(), "\
+digraph intcode {
+    node [shape=box, fontname=\"monospace\"];
+    b0 [label=\"    0: JZ   #0, @0\\lcomputed jump\\l\", color=red];
+}
+");
+        fn nop(_: &mut crate::Operands) -> Result<crate::Flow, crate::IntcodeError> {
+            Ok(crate::Flow::Next)
+        }
+        let mut instructions = InstructionSet::standard();
+        instructions.define(Instruction { opcode: 10, mnemonic: "N\"O\\P", operand_count: 0, write_operand: None, implementation: nop }).unwrap();
+        let graph = control_flow_graph_with(&[10, 99], &instructions);
+        assert!(graph.to_dot().contains("b0 [label=\"    0: N\\\"O\\\\P\\l    1: HLT\\l\"];"), "{}", graph.to_dot());
+    }
+}
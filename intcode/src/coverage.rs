@@ -0,0 +1,183 @@
+use std::collections::{BTreeSet, HashSet};
+
+use super::disassembler::decode_table;
+use super::{Instruction, InstructionSet, Line, TraceRecord, Tracer, INSTRUCTIONS};
+
+/// How a memory cell was used during the traced runs.
+#[derive(std::fmt::Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellUse {
+    /// Part of an executed instruction, opcode or operand.
+    Executed,
+    /// Only accessed by instructions as a parameter.
+    Data { read: bool, written: bool },
+    Untouched,
+}
+
+/// Records which cells were executed as instructions and which were read or written as data.
+/// Pass it to `run_traced`, `Context::resume_traced` or `Context::step_traced`; coverage of
+/// several runs, for example with different inputs, accumulates.
+#[derive(std::fmt::Debug, Clone, Default)]
+pub struct Coverage {
+    /// Addresses of executed instructions.
+    instructions: BTreeSet<usize>,
+    executed: HashSet<usize>,
+    read: HashSet<usize>,
+    written: HashSet<usize>,
+    /// Raw opcodes executed, which includes the parameter modes.
+    opcodes: BTreeSet<i64>,
+}
+
+impl Tracer for Coverage {
+    fn trace(&mut self, record: &TraceRecord) {
+        self.instructions.insert(record.ip);
+        self.executed.extend(record.ip..=record.ip + record.parameters.len());
+        self.opcodes.insert(record.opcode);
+        for parameter in &record.parameters {
+            if let Some(address) = parameter.address {
+                if parameter.read {
+                    self.read.insert(address);
+                }
+                if parameter.write {
+                    self.written.insert(address);
+                }
+            }
+        }
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    pub fn cell(&self, address: usize) -> CellUse {
+        if self.executed.contains(&address) {
+            return CellUse::Executed;
+        }
+        match (self.read.contains(&address), self.written.contains(&address)) {
+            (false, false) => CellUse::Untouched,
+            (read, written) => CellUse::Data { read, written },
+        }
+    }
+
+    /// Whether an instruction starting at `address` was executed.
+    pub fn executed(&self, address: usize) -> bool {
+        self.instructions.contains(&address)
+    }
+
+    /// The raw opcodes executed, so the opcode and mode combinations a test exercises.
+    pub fn opcodes(&self) -> impl Iterator<Item = i64> + '_ {
+        self.opcodes.iter().copied()
+    }
+
+    /// Disassembly of `program` with the use of every line in front of it: `x` for executed
+    /// instructions, `r` and `w` for data that was read or written and `-` for lines never
+    /// touched. Executed instructions are always decoded where they were executed, even when
+    /// a linear disassembly would have decoded across them. A summary follows the listing.
+    pub fn annotate(&self, program: &[i64]) -> String {
+        self.annotate_table(&INSTRUCTIONS, program)
+    }
+
+    /// Like `annotate`, with the opcodes of `instructions`.
+    pub fn annotate_with(&self, program: &[i64], instructions: &InstructionSet) -> String {
+        self.annotate_table(&instructions.instructions, program)
+    }
+
+    fn annotate_table(&self, instructions: &[Option<Instruction>; 100], program: &[i64]) -> String {
+        let mut annotated = String::new();
+        let mut counts = [0; 3];
+        let mut address = 0;
+        while address < program.len() {
+            let mut line = decode_table(instructions, program, address);
+            let overlaps = (address + 1..address + line.size()).any(|cell| self.executed(cell));
+            if overlaps && !self.executed(address) {
+                line = Line::Data { address, value: program[address] };
+            }
+            let cells: Vec<CellUse> = (address..address + line.size()).map(|cell| self.cell(cell)).collect();
+            let marker = if self.executed(address) {
+                "x "
+            } else {
+                let read = cells.iter().any(|cell| matches!(cell, CellUse::Data { read: true, .. }));
+                let written = cells.iter().any(|cell| matches!(cell, CellUse::Data { written: true, .. }));
+                match (read, written) {
+                    (true, true) => "rw",
+                    (true, false) => "r ",
+                    (false, true) => " w",
+                    (false, false) => "- ",
+                }
+            };
+            for cell in cells {
+                counts[match cell { CellUse::Executed => 0, CellUse::Data { .. } => 1, CellUse::Untouched => 2 }] += 1;
+            }
+            annotated.push_str(&format!("{} {:>5}: {}\n", marker, address, line));
+            address += line.size();
+        }
+        let opcodes: Vec<String> = self.opcodes.iter().map(|opcode| opcode.to_string()).collect();
+        annotated.push_str(&format!("; {} cells executed, {} data, {} untouched; opcodes {}\n",
+                                    counts[0], counts[1], counts[2], opcodes.join(" ")));
+        annotated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run_traced, Context};
+    use std::collections::VecDeque;
+
+    // Outputs cell 13 when the input is 0 and cell 14 otherwise. Cell 8 decodes as an ADD
+    // that covers the second OUT.
+    const PROGRAM: [i64; 15] = [3, 15, 1005, 15, 9, 4, 13, 99, 1, 4, 14, 99, 0, 7, 8];
+
+    fn cover(inputs: &[i64]) -> Coverage {
+        let mut coverage = Coverage::new();
+        for &input in inputs {
+            let mut context = Context::new(PROGRAM.to_vec(), VecDeque::from(vec!(input)), Vec::new());
+            run_traced(&mut context, &mut coverage).unwrap();
+        }
+        coverage
+    }
+
+    #[test]
+    fn test_cell_use() {
+        let coverage = cover(&[0]);
+        assert_eq!(coverage.cell(2), CellUse::Executed);
+        assert_eq!(coverage.cell(13), CellUse::Data { read: true, written: false });
+        assert_eq!(coverage.cell(15), CellUse::Data { read: true, written: true });
+        assert_eq!(coverage.cell(9), CellUse::Untouched);
+        assert_eq!(coverage.opcodes().collect::<Vec<i64>>(), vec!(3, 4, 99, 1005));
+
+        // JNZ #0, 7 is not taken, so its target in cell 7 is never read.
+        let mut coverage = Coverage::new();
+        run_traced(&mut Context::new(vec!(105, 0, 7, 99, 0, 0, 0, 0), (), Vec::new()), &mut coverage).unwrap();
+        assert_eq!(coverage.cell(7), CellUse::Untouched);
+    }
+
+    #[test]
+    fn test_annotate() {
+        assert_eq!(cover(&[0]).annotate(&PROGRAM), "\
+x      0: IN   15
+x      2: JNZ  15, #9
+x      5: OUT  13
+x      7: HLT
+-      8: ADD  4, 14, 99
+-     12: DATA 0
+r     13: DATA 7
+-     14: DATA 8
+; 8 cells executed, 1 data, 6 untouched; opcodes 3 4 99 1005
+");
+        assert_eq!(cover(&[0, 1]).annotate(&PROGRAM), "\
+x      0: IN   15
+x      2: JNZ  15, #9
+x      5: OUT  13
+x      7: HLT
+-      8: DATA 1
+x      9: OUT  14
+x     11: HLT
+-     12: DATA 0
+r     13: DATA 7
+r     14: DATA 8
+; 11 cells executed, 2 data, 2 untouched; opcodes 3 4 99 1005
+");
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+
+use super::{Context, Input, IntcodeError, Output, Status};
+use super::disassembler::decode_memory;
+
+/// Reason the debugger handed control back.
+#[derive(std::fmt::Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Stop {
+    /// A single step finished without anything noteworthy happening.
+    Stepped,
+    Breakpoint(usize),
+    /// A watched cell changed value.
+    Watchpoint { address: usize, old: i64, new: i64 },
+    Output(i64),
+    NeedsInput,
+    Halted,
+}
+
+impl std::fmt::Display for Stop {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Stop::Stepped => write!(fmt, "stepped"),
+            Stop::Breakpoint(address) => write!(fmt, "breakpoint at {}", address),
+            Stop::Watchpoint { address, old, new } => write!(fmt, "watchpoint at {}: {} -> {}", address, old, new),
+            Stop::Output(value) => write!(fmt, "output {}", value),
+            Stop::NeedsInput => write!(fmt, "waiting for input"),
+            Stop::Halted => write!(fmt, "halted"),
+        }
+    }
+}
+
+/// Runs a context instruction by instruction, stopping at breakpoints and watchpoints.
+/// Outputs are always forwarded to the output sink of the context, as `run` does.
+pub struct Debugger<I = (), O = ()> {
+    context: Context<I, O>,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<usize>,
+}
+
+impl<I: Input, O: Output> Debugger<I, O> {
+    pub fn new(context: Context<I, O>) -> Debugger<I, O> {
+        Debugger {
+            context,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+        }
+    }
+
+    pub fn context(&self) -> &Context<I, O> {
+        &self.context
+    }
+
+    pub fn context_mut(&mut self) -> &mut Context<I, O> {
+        &mut self.context
+    }
+
+    pub fn into_context(self) -> Context<I, O> {
+        self.context
+    }
+
+    pub fn add_breakpoint(&mut self, address: usize) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &usize> {
+        self.breakpoints.iter()
+    }
+
+    pub fn add_watchpoint(&mut self, address: usize) {
+        self.watchpoints.insert(address);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: usize) -> bool {
+        self.watchpoints.remove(&address)
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = &usize> {
+        self.watchpoints.iter()
+    }
+
+    fn peek(&self, address: usize) -> i64 {
+        self.context.memory().get(address).unwrap_or(0)
+    }
+
+    /// Executes exactly one instruction (or reports that it cannot, for lack of input).
+    pub fn step(&mut self) -> Result<Stop, IntcodeError> {
+        let watched: Vec<(usize, i64)> = self.watchpoints.iter().map(|&address| (address, self.peek(address))).collect();
+        let status = self.context.step()?;
+        for (address, old) in watched {
+            let new = self.peek(address);
+            if new != old {
+                return Ok(Stop::Watchpoint { address, old, new });
+            }
+        }
+        Ok(match status {
+            None => Stop::Stepped,
+            Some(Status::Output(value)) => {
+                if self.context.output.write(value).is_err() {
+                    return Err(IntcodeError::OutputClosed { ip: self.context.ip() });
+                }
+                Stop::Output(value)
+            }
+            Some(Status::NeedsInput) => Stop::NeedsInput,
+            Some(Status::Halted) => Stop::Halted,
+        })
+    }
+
+    /// Runs until a breakpoint or watchpoint is hit, input is needed or the program halts.
+    /// A breakpoint on the current instruction does not stop it from executing.
+    pub fn cont(&mut self) -> Result<Stop, IntcodeError> {
+        self.run_until(false)
+    }
+
+    /// Like `cont`, but also stops after the next output.
+    pub fn run_to_output(&mut self) -> Result<Stop, IntcodeError> {
+        self.run_until(true)
+    }
+
+    fn run_until(&mut self, stop_on_output: bool) -> Result<Stop, IntcodeError> {
+        let mut first = true;
+        loop {
+            let ip = self.context.ip();
+            if !first && self.breakpoints.contains(&ip) {
+                return Ok(Stop::Breakpoint(ip));
+            }
+            first = false;
+            match self.step()? {
+                Stop::Stepped => {}
+                Stop::Output(_) if !stop_on_output => {}
+                stop => return Ok(stop),
+            }
+        }
+    }
+}
+
+const HELP: &str = "\
+s [n]            step n instructions (default 1)
+c                continue to the next breakpoint, watchpoint, input request or halt
+o                run to the next output
+b [addr]         set a breakpoint, or list breakpoints
+db addr          delete a breakpoint
+w [addr]         set a watchpoint, or list watchpoints
+dw addr          delete a watchpoint
+i value...       queue input values
+m addr [count]   show memory
+set addr value   write memory
+rb [value]       show or set the relative base
+ip [addr]        show or set the instruction pointer
+l [addr] [count] disassemble (default: from the instruction pointer)
+q                quit
+";
+
+/// Line-oriented front end for a debugger: reads commands from `input` until `q` or end of
+/// input and writes responses to `output`. Type `h` for the list of commands.
+pub fn repl<I: Input, O: Output>(debugger: &mut Debugger<I, O>, input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+    write!(output, "(icdb) ")?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.first() == Some(&"q") {
+            break;
+        }
+        match command(debugger, &words, &mut output) {
+            Ok(()) => {}
+            Err(CommandError::Usage(message)) => writeln!(output, "{}", message)?,
+            Err(CommandError::Io(error)) => return Err(error),
+        }
+        write!(output, "(icdb) ")?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+enum CommandError {
+    Usage(String),
+    Io(std::io::Error),
+}
+
+impl From<std::io::Error> for CommandError {
+    fn from(error: std::io::Error) -> CommandError {
+        CommandError::Io(error)
+    }
+}
+
+fn argument<T: std::str::FromStr>(words: &[&str], index: usize) -> Result<Option<T>, CommandError> {
+    match words.get(index) {
+        Some(word) => word.parse().map(Some).map_err(|_| CommandError::Usage(format!("invalid argument {}", word))),
+        None => Ok(None),
+    }
+}
+
+fn required<T: std::str::FromStr>(words: &[&str], index: usize) -> Result<T, CommandError> {
+    argument(words, index)?.ok_or_else(|| CommandError::Usage(format!("{} needs an argument, type h for help", words[0])))
+}
+
+fn command<I: Input, O: Output>(debugger: &mut Debugger<I, O>, words: &[&str], output: &mut impl Write) -> Result<(), CommandError> {
+    match words.first() {
+        None => {}
+        Some(&"h") => write!(output, "{}", HELP)?,
+        Some(&"s") => {
+            let mut stop = Ok(Stop::Stepped);
+            for _ in 0..argument::<usize>(words, 1)?.unwrap_or(1) {
+                stop = debugger.step();
+                if stop != Ok(Stop::Stepped) {
+                    break;
+                }
+            }
+            report(debugger, stop, output)?;
+        }
+        Some(&"c") => {
+            let stop = debugger.cont();
+            report(debugger, stop, output)?;
+        }
+        Some(&"o") => {
+            let stop = debugger.run_to_output();
+            report(debugger, stop, output)?;
+        }
+        Some(&"b") => match argument(words, 1)? {
+            Some(address) => debugger.add_breakpoint(address),
+            None => writeln!(output, "breakpoints: {:?}", debugger.breakpoints().collect::<Vec<_>>())?,
+        },
+        Some(&"db") => {
+            let address = required(words, 1)?;
+            if !debugger.remove_breakpoint(address) {
+                return Err(CommandError::Usage(format!("no breakpoint at {}", address)));
+            }
+        }
+        Some(&"w") => match argument(words, 1)? {
+            Some(address) => debugger.add_watchpoint(address),
+            None => writeln!(output, "watchpoints: {:?}", debugger.watchpoints().collect::<Vec<_>>())?,
+        },
+        Some(&"dw") => {
+            let address = required(words, 1)?;
+            if !debugger.remove_watchpoint(address) {
+                return Err(CommandError::Usage(format!("no watchpoint at {}", address)));
+            }
+        }
+        Some(&"i") => {
+            for index in 1..words.len() {
+                let value = required(words, index)?;
+                debugger.context_mut().push_input(value);
+            }
+        }
+        Some(&"m") => {
+            let address: usize = required(words, 1)?;
+            let count: usize = argument(words, 2)?.unwrap_or(1);
+            let end = address.checked_add(count).ok_or_else(|| CommandError::Usage("address out of range".to_string()))?;
+            let values: Vec<String> = (address..end).map(|a| debugger.peek(a).to_string()).collect();
+            writeln!(output, "{}: {}", address, values.join(" "))?;
+        }
+        Some(&"set") => {
+            let address: usize = required(words, 1)?;
+            let value = required(words, 2)?;
+            // Memory is at most `usize::MAX` cells long, so the last address cannot be written.
+            if address == usize::MAX {
+                return Err(CommandError::Usage("address out of range".to_string()));
+            }
+            debugger.context_mut().write(address, value);
+        }
+        Some(&"rb") => match argument(words, 1)? {
+            Some(value) => debugger.context_mut().set_relative_base(value),
+            None => writeln!(output, "relative base {}", debugger.context().relative_base())?,
+        },
+        Some(&"ip") => match argument(words, 1)? {
+            Some(address) => debugger.context_mut().set_ip(address),
+            None => location(debugger, output)?,
+        },
+        Some(&"l") => {
+            let context = debugger.context();
+            let memory = context.memory();
+            let mut address = argument(words, 1)?.unwrap_or_else(|| context.ip());
+            for _ in 0..argument(words, 2)?.unwrap_or(10) {
+                if address >= memory.len() {
+                    break;
+                }
+                let line = decode_memory(context.machine.instruction_table(), memory, address);
+                writeln!(output, "{} {:>5}: {}", if address == context.ip() { "=>" } else { "  " }, address, line)?;
+                address += line.size();
+            }
+        }
+        Some(other) => return Err(CommandError::Usage(format!("unknown command {}, type h for help", other))),
+    }
+    Ok(())
+}
+
+fn report<I: Input, O: Output>(debugger: &Debugger<I, O>, stop: Result<Stop, IntcodeError>, output: &mut impl Write) -> std::io::Result<()> {
+    match stop {
+        Ok(stop) => writeln!(output, "{}", stop)?,
+        Err(error) => writeln!(output, "error: {}", error)?,
+    }
+    location(debugger, output)
+}
+
+fn location<I: Input, O: Output>(debugger: &Debugger<I, O>, output: &mut impl Write) -> std::io::Result<()> {
+    let context = debugger.context();
+    let ip = context.ip();
+    if ip < context.memory().len() {
+        writeln!(output, "=> {:>5}: {}", ip, decode_memory(context.machine.instruction_table(), context.memory(), ip))
+    } else {
+        writeln!(output, "=> {:>5}: <outside memory>", ip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, Backend, MemoryConfig};
+
+    fn counter() -> Vec<i64> {
+        assemble("
+            loop:   ADD  count, #1, count
+                    OUT  count
+                    LT   count, #3, flag
+                    JNZ  flag, #loop
+                    HLT
+            count:  DATA 0
+            flag:   DATA 0").unwrap()
+    }
+
+    #[test]
+    fn test_breakpoint_and_step() {
+        let mut debugger = Debugger::new(Context::new(counter(), (), Vec::new()));
+        debugger.add_breakpoint(4);
+        assert_eq!(debugger.cont(), Ok(Stop::Breakpoint(4)));
+        assert_eq!(debugger.step(), Ok(Stop::Output(1)));
+        assert_eq!(debugger.cont(), Ok(Stop::Breakpoint(4)));
+        assert!(debugger.remove_breakpoint(4));
+        assert_eq!(debugger.cont(), Ok(Stop::Halted));
+        assert_eq!(debugger.context().output(), &vec!(1, 2, 3));
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let mut debugger = Debugger::new(Context::new(counter(), (), Vec::new()));
+        debugger.add_watchpoint(14);
+        assert_eq!(debugger.cont(), Ok(Stop::Watchpoint { address: 14, old: 0, new: 1 }));
+        assert_eq!(debugger.context().ip(), 4);
+    }
+
+    #[test]
+    fn test_run_to_output_and_edit_memory() {
+        let mut debugger = Debugger::new(Context::new(counter(), (), Vec::new()));
+        assert_eq!(debugger.run_to_output(), Ok(Stop::Output(1)));
+        debugger.context_mut().write(14, -10);
+        assert_eq!(debugger.run_to_output(), Ok(Stop::Output(-9)));
+        assert_eq!(debugger.run_to_output(), Ok(Stop::Output(-8)));
+    }
+
+    #[test]
+    fn test_repl() {
+        let mut debugger = Debugger::new(Context::with_memory(vec!(3, 7, 204, 7, 99)));
+        let mut output = Vec::new();
+        repl(&mut debugger, "c\ni 5\nrb 2\nset 9 42\no\nm 7 3\nbogus\nm 18446744073709551615 2\nset 18446744073709551615 1\ns -1\nq\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "\
+(icdb) waiting for input
+=>     0: IN   7
+(icdb) (icdb) (icdb) (icdb) output 42
+=>     4: HLT
+(icdb) 7: 5 0 42
+(icdb) unknown command bogus, type h for help
+(icdb) address out of range
+(icdb) address out of range
+(icdb) invalid argument -1
+(icdb) ");
+    }
+
+    #[test]
+    fn test_repl_with_sparse_memory() {
+        // Writes the input to the cell at 10^12, then halts.
+        let mut context = Context::with_memory(vec!(3, 1_000_000_000_000, 99));
+        context.set_memory_config(MemoryConfig { backend: Backend::Sparse, ..MemoryConfig::default() });
+        let mut debugger = Debugger::new(context);
+        let mut output = Vec::new();
+        repl(&mut debugger, "i 7\ns\nl 999999999999 3\nq\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "\
+(icdb) (icdb) stepped
+=>     2: HLT
+(icdb)    999999999999: DATA 0
+   1000000000000: DATA 7
+(icdb) ");
+    }
+}
@@ -0,0 +1,210 @@
+use super::{split_instruction, Instruction, InstructionSet, Memory, INSTRUCTIONS};
+
+/// Addressing mode of an operand, written as a sigil in front of the value:
+/// none for position mode, `#` for immediate mode and `@` for relative mode.
+#[derive(std::fmt::Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl Mode {
+    fn from_digit(digit: u8) -> Option<Mode> {
+        match digit {
+            0 => Some(Mode::Position),
+            1 => Some(Mode::Immediate),
+            2 => Some(Mode::Relative),
+            _ => None,
+        }
+    }
+
+    /// The mode digit as it appears in an opcode.
+    pub fn digit(self) -> i64 {
+        match self {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Position => "position",
+            Mode::Immediate => "immediate",
+            Mode::Relative => "relative",
+        }
+    }
+
+    pub fn sigil(self) -> &'static str {
+        match self {
+            Mode::Position => "",
+            Mode::Immediate => "#",
+            Mode::Relative => "@",
+        }
+    }
+}
+
+#[derive(std::fmt::Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Operand {
+    pub mode: Mode,
+    pub value: i64,
+}
+
+impl std::fmt::Display for Operand {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(fmt, "{}{}", self.mode.sigil(), self.value)
+    }
+}
+
+/// One decoded entry of a program: an instruction, or a single cell that does not decode.
+#[derive(std::fmt::Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+    Instruction { address: usize, opcode: i64, mnemonic: &'static str, operands: Vec<Operand> },
+    Data { address: usize, value: i64 },
+}
+
+impl Line {
+    pub fn address(&self) -> usize {
+        match self {
+            Line::Instruction { address, .. } | Line::Data { address, .. } => *address,
+        }
+    }
+
+    /// Number of memory cells covered by this line.
+    pub fn size(&self) -> usize {
+        match self {
+            Line::Instruction { operands, .. } => operands.len() + 1,
+            Line::Data { .. } => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for Line {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Line::Instruction { mnemonic, operands, .. } => {
+                let operands: Vec<String> = operands.iter().map(|operand| operand.to_string()).collect();
+                write!(fmt, "{}", format!("{:<4} {}", mnemonic, operands.join(", ")).trim_end())
+            }
+            Line::Data { value, .. } => write!(fmt, "DATA {}", value),
+        }
+    }
+}
+
+/// Decodes the cell at `address`. Cells that do not form a complete instruction with valid
+/// mode digits (and no superfluous ones) are returned as data, as are addresses beyond the
+/// end of the program, which read as 0.
+pub fn decode(program: &[i64], address: usize) -> Line {
+    decode_table(&INSTRUCTIONS, program, address)
+}
+
+/// Like `decode`, with the opcodes of `instructions`.
+pub fn decode_with(program: &[i64], address: usize, instructions: &InstructionSet) -> Line {
+    decode_table(&instructions.instructions, program, address)
+}
+
+pub(crate) fn decode_table(instructions: &[Option<Instruction>; 100], program: &[i64], address: usize) -> Line {
+    decode_cells(instructions, |address| program.get(address).copied(), address)
+}
+
+/// Like `decode`, reading only the cells of the instruction from the memory of a machine.
+pub(crate) fn decode_memory(instructions: &[Option<Instruction>; 100], memory: &Memory, address: usize) -> Line {
+    decode_cells(instructions, |address| memory.get(address), address)
+}
+
+fn decode_cells(instructions: &[Option<Instruction>; 100], cell: impl Fn(usize) -> Option<i64>, address: usize) -> Line {
+    let value = cell(address).unwrap_or(0);
+    let data = Line::Data { address, value };
+    if value < 0 {
+        return data;
+    }
+    let (opcode, modes) = split_instruction(value as usize);
+    let instruction = match &instructions[opcode] {
+        Some(instruction) => instruction,
+        None => return data,
+    };
+    if modes.len() > instruction.operand_count {
+        return data;
+    }
+    let mut operands = Vec::with_capacity(instruction.operand_count);
+    for i in 0..instruction.operand_count {
+        match (Mode::from_digit(modes.mode(i)), address.checked_add(1 + i).and_then(&cell)) {
+            (Some(mode), Some(value)) => operands.push(Operand { mode, value }),
+            _ => return data,
+        }
+    }
+    Line::Instruction { address, opcode: value, mnemonic: instruction.mnemonic, operands }
+}
+
+/// Decodes a whole program front to back, continuing after every line.
+pub fn disassemble(program: &[i64]) -> Vec<Line> {
+    disassemble_table(&INSTRUCTIONS, program)
+}
+
+/// Like `disassemble`, with the opcodes of `instructions`.
+pub fn disassemble_with(program: &[i64], instructions: &InstructionSet) -> Vec<Line> {
+    disassemble_table(&instructions.instructions, program)
+}
+
+fn disassemble_table(instructions: &[Option<Instruction>; 100], program: &[i64]) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut address = 0;
+    while address < program.len() {
+        let line = decode_table(instructions, program, address);
+        address += line.size();
+        lines.push(line);
+    }
+    lines
+}
+
+/// Listing of a program with one line per instruction or data cell, prefixed by its address.
+pub fn listing(program: &[i64]) -> String {
+    format_listing(&disassemble(program))
+}
+
+/// Like `listing`, with the opcodes of `instructions`; `assemble_with` reads it back.
+pub fn listing_with(program: &[i64], instructions: &InstructionSet) -> String {
+    format_listing(&disassemble_with(program, instructions))
+}
+
+fn format_listing(lines: &[Line]) -> String {
+    lines
+        .iter()
+        .map(|line| format!("{:>5}: {}\n", line.address(), line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_modes() {
+        assert_eq!(decode(&[21101, 1, -2, 3], 0), Line::Instruction {
+            address: 0,
+            opcode: 21101,
+            mnemonic: "ADD",
+            operands: vec!(
+                Operand { mode: Mode::Immediate, value: 1 },
+                Operand { mode: Mode::Immediate, value: -2 },
+                Operand { mode: Mode::Relative, value: 3 }),
+        });
+    }
+
+    #[test]
+    fn test_decode_falls_back_to_data() {
+        assert_eq!(decode(&[42], 0), Line::Data { address: 0, value: 42 });
+        assert_eq!(decode(&[-1], 0), Line::Data { address: 0, value: -1 });
+        assert_eq!(decode(&[1, 2, 3], 0), Line::Data { address: 0, value: 1 });
+        assert_eq!(decode(&[301, 2, 3, 4], 0), Line::Data { address: 0, value: 301 });
+        assert_eq!(decode(&[10099], 0), Line::Data { address: 0, value: 10099 });
+        assert_eq!(decode(&[99], 5), Line::Data { address: 5, value: 0 });
+    }
+
+    #[test]
+    fn test_listing() {
+        assert_eq!(listing(&[1002, 4, 3, 4, 33, 109, -1, 204, 1, 99, 7]),
+                   "    0: MUL  4, #3, 4\n    4: DATA 33\n    5: ARB  #-1\n    7: OUT  @1\n    9: HLT\n   10: DATA 7\n");
+    }
+}
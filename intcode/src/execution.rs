@@ -0,0 +1,97 @@
+use std::collections::VecDeque;
+
+use super::{run, Context, IntcodeError, Limits, Memory};
+
+/// How a program run by `execute` ended.
+#[derive(std::fmt::Debug, PartialEq, Eq, Clone)]
+pub enum ExitStatus {
+    Halted,
+    /// The program asked for more input than it was given.
+    NeedsInput,
+    Faulted(IntcodeError),
+    /// A patch addressed a cell beyond the end of the program, so nothing was run.
+    InvalidPatch { address: usize },
+}
+
+/// The outcome of `execute`: memory and outputs as they were when the program stopped.
+#[derive(std::fmt::Debug, PartialEq, Eq, Clone)]
+pub struct Execution {
+    pub status: ExitStatus,
+    pub memory: Memory,
+    pub outputs: Vec<i64>,
+    /// Address of the instruction the program stopped at.
+    pub ip: usize,
+}
+
+impl Execution {
+    pub fn halted(&self) -> bool {
+        self.status == ExitStatus::Halted
+    }
+
+    /// A cell of the final memory; cells beyond its end read as 0.
+    pub fn read(&self, address: usize) -> i64 {
+        self.memory.get(address).unwrap_or(0)
+    }
+}
+
+/// Runs `program` with `patches`, pairs of address and value written before it starts, on
+/// `inputs` until it stops. Patches must fall inside the program; cells beyond its end start
+/// out as 0 anyway. Faults are reported in the status, never as a panic.
+pub fn execute(program: &[i64], patches: &[(usize, i64)], inputs: &[i64]) -> Execution {
+    execute_with_limits(program, patches, inputs, Limits::default())
+}
+
+/// Like `execute`, stopping the program at `limits` with `IntcodeError::LimitExceeded`.
+pub fn execute_with_limits(program: &[i64], patches: &[(usize, i64)], inputs: &[i64], limits: Limits) -> Execution {
+    let mut context = Context::new(program.to_vec(), inputs.iter().copied().collect::<VecDeque<i64>>(), Vec::new());
+    context.set_limits(limits);
+    let status = match patches.iter().find(|&&(address, _)| address >= program.len()) {
+        Some(&(address, _)) => ExitStatus::InvalidPatch { address },
+        None => {
+            for &(address, value) in patches {
+                context.write(address, value);
+            }
+            match run(&mut context) {
+                Ok(()) => ExitStatus::Halted,
+                Err(IntcodeError::InputClosed { .. }) => ExitStatus::NeedsInput,
+                Err(error) => ExitStatus::Faulted(error),
+            }
+        }
+    };
+    let Context { machine, output, .. } = context;
+    Execution { status, memory: machine.memory, outputs: output, ip: machine.ip }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patch_and_inspect() {
+        let execution = execute(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50], &[(1, 10), (2, 11)], &[]);
+        assert!(execution.halted());
+        assert_eq!(execution.read(0), 4500);
+        assert_eq!(execution.read(3), 90);
+        assert_eq!(execution.read(100), 0);
+
+        let execution = execute(&[3, 9, 4, 9, 3, 9, 99], &[], &[42]);
+        assert_eq!(execution.status, ExitStatus::NeedsInput);
+        assert_eq!((execution.outputs, execution.ip, execution.memory.len()), (vec!(42), 4, 10));
+
+        let execution = execute(&[99], &[(0, 99), (usize::MAX, 1)], &[]);
+        assert_eq!(execution.status, ExitStatus::InvalidPatch { address: usize::MAX });
+        assert_eq!((execution.memory.to_vec(), execution.ip), (vec!(99), 0));
+    }
+
+    #[test]
+    fn test_fault() {
+        let execution = execute(&[104, 7, 1, 0, 0, 0, 99], &[(2, 42)], &[]);
+        assert_eq!(execution.status, ExitStatus::Faulted(IntcodeError::UnknownOpcode { ip: 2, opcode: 42 }));
+        assert_eq!(execution.outputs, vec!(7));
+        assert_eq!(execution.memory.to_vec(), vec!(104, 7, 42, 0, 0, 0, 99));
+
+        let limits = Limits { max_instructions: Some(5), ..Limits::default() };
+        let execution = execute_with_limits(&[1105, 1, 0], &[], &[], limits);
+        assert_eq!(execution.status, ExitStatus::Faulted(IntcodeError::LimitExceeded { ip: 0, limit: crate::Limit::Instructions(5), executed: 5 }));
+    }
+}
@@ -0,0 +1,237 @@
+use std::sync::Arc;
+
+use super::{Context, Input, Instruction, IntcodeError, Machine, Output, Parameter, INSTRUCTIONS, MAX_OPERANDS};
+
+/// The operands of the instruction being executed, as seen by its implementation. Operands
+/// are decoded with the usual parameter modes, so `read` and `write` work in position,
+/// immediate and relative mode alike.
+pub struct Operands<'a> {
+    pub(crate) machine: &'a mut Machine,
+    pub(crate) parameters: &'a [Parameter],
+}
+
+impl Operands<'_> {
+    pub fn len(&self) -> usize {
+        self.parameters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parameters.is_empty()
+    }
+
+    /// Value of the operand at `index`.
+    #[inline]
+    pub fn read(&mut self, index: usize) -> Result<i64, IntcodeError> {
+        self.machine.read_memory_parameter(self.parameters, index)
+    }
+
+    /// Writes to the cell the operand at `index` refers to; an operand in immediate mode
+    /// faults with `IntcodeError::ImmediateWrite`.
+    #[inline]
+    pub fn write(&mut self, index: usize, value: i64) -> Result<(), IntcodeError> {
+        self.machine.write_memory_parameter(self.parameters, index, value)
+    }
+
+    /// Value of the operand at `index` as a jump target, faulting when it is negative.
+    #[inline]
+    pub fn jump_target(&mut self, index: usize) -> Result<usize, IntcodeError> {
+        self.machine.jump_target(self.parameters, index)
+    }
+
+    /// Takes the next queued input value. Return `Flow::Wait` when there is none.
+    #[inline]
+    pub fn input(&mut self) -> Option<i64> {
+        self.machine.read_input()
+    }
+
+    /// Produces an output value. An instruction produces at most one; a second faults with
+    /// `IntcodeError::DuplicateOutput`.
+    #[inline]
+    pub fn output(&mut self, value: i64) -> Result<(), IntcodeError> {
+        self.machine.write_output(value)
+    }
+
+    /// Address of the instruction being executed.
+    pub fn ip(&self) -> usize {
+        self.machine.ip
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.machine.relative_base
+    }
+
+    pub fn set_relative_base(&mut self, relative_base: i64) {
+        self.machine.relative_base = relative_base;
+    }
+}
+
+/// Why `InstructionSet::define` rejected an instruction.
+#[derive(std::fmt::Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DefinitionError {
+    /// The opcode has more than two digits.
+    Opcode { mnemonic: &'static str, opcode: usize },
+    /// More than `MAX_OPERANDS` operands.
+    OperandCount { mnemonic: &'static str, operand_count: usize },
+    /// The write operand is not one of the operands.
+    WriteOperand { mnemonic: &'static str },
+    /// Another opcode has the same mnemonic.
+    Mnemonic { mnemonic: &'static str, opcode: usize },
+}
+
+impl std::fmt::Display for DefinitionError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            DefinitionError::Opcode { mnemonic, opcode } =>
+                write!(fmt, "opcode {} of {} has more than two digits", opcode, mnemonic),
+            DefinitionError::OperandCount { mnemonic, operand_count } =>
+                write!(fmt, "{} has {} operands, more than {}", mnemonic, operand_count, MAX_OPERANDS),
+            DefinitionError::WriteOperand { mnemonic } =>
+                write!(fmt, "write operand of {} out of range", mnemonic),
+            DefinitionError::Mnemonic { mnemonic, opcode } =>
+                write!(fmt, "{} is already opcode {}", mnemonic, opcode),
+        }
+    }
+}
+
+/// Opcodes a machine executes, indexed by basic opcode. Starts out as the standard intcode
+/// instruction set; install it with `Context::set_instruction_set`.
+#[derive(Clone)]
+pub struct InstructionSet {
+    pub(crate) instructions: [Option<Instruction>; 100],
+}
+
+impl Default for InstructionSet {
+    fn default() -> InstructionSet {
+        InstructionSet::standard()
+    }
+}
+
+impl std::fmt::Debug for InstructionSet {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl InstructionSet {
+    /// Opcodes 1 to 9 and 99.
+    pub fn standard() -> InstructionSet {
+        InstructionSet { instructions: INSTRUCTIONS }
+    }
+
+    pub fn empty() -> InstructionSet {
+        InstructionSet { instructions: [None; 100] }
+    }
+
+    /// Defines an opcode, returning the definition it replaces. Mnemonics are compared without
+    /// regard to case, as the assembler reads them.
+    pub fn define(&mut self, instruction: Instruction) -> Result<Option<Instruction>, DefinitionError> {
+        let mnemonic = instruction.mnemonic;
+        if instruction.opcode >= 100 {
+            return Err(DefinitionError::Opcode { mnemonic, opcode: instruction.opcode });
+        }
+        if instruction.operand_count > MAX_OPERANDS {
+            return Err(DefinitionError::OperandCount { mnemonic, operand_count: instruction.operand_count });
+        }
+        if instruction.write_operand.is_some_and(|index| index >= instruction.operand_count) {
+            return Err(DefinitionError::WriteOperand { mnemonic });
+        }
+        if let Some(other) = self.iter().find(|other| other.opcode != instruction.opcode && other.mnemonic.eq_ignore_ascii_case(mnemonic)) {
+            return Err(DefinitionError::Mnemonic { mnemonic, opcode: other.opcode });
+        }
+        Ok(self.instructions[instruction.opcode].replace(instruction))
+    }
+
+    pub fn remove(&mut self, opcode: usize) -> Option<Instruction> {
+        self.instructions.get_mut(opcode).and_then(Option::take)
+    }
+
+    pub fn get(&self, opcode: usize) -> Option<&Instruction> {
+        self.instructions.get(opcode).and_then(Option::as_ref)
+    }
+
+    /// Defined instructions in order of opcode.
+    pub fn iter(&self) -> impl Iterator<Item = &Instruction> {
+        self.instructions.iter().flatten()
+    }
+}
+
+impl<I: Input, O: Output> Context<I, O> {
+    /// Executes with `instructions` from now on. Forks share the instruction set.
+    pub fn set_instruction_set(&mut self, instructions: InstructionSet) {
+        self.machine.instructions = Some(Arc::new(instructions));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run, run_traced, Flow, Status, TextTracer};
+
+    fn subtract(operands: &mut Operands) -> Result<Flow, IntcodeError> {
+        let value = operands.read(0)? - operands.read(1)?;
+        operands.write(2, value)?;
+        Ok(Flow::Next)
+    }
+
+    const SUB: Instruction = Instruction { opcode: 10, mnemonic: "SUB", operand_count: 3, write_operand: Some(2), implementation: subtract };
+
+    #[test]
+    fn test_custom_opcode() {
+        let mut instructions = InstructionSet::standard();
+        assert!(instructions.define(SUB).unwrap().is_none());
+        // SUB #50, 9, @1 with relative base 10 writes to cell 11.
+        let mut context = Context::new(vec!(109, 10, 20110, 50, 9, 1, 4, 11, 99, 8, 0, 0), (), Vec::new());
+        context.set_instruction_set(instructions);
+        let mut trace = TextTracer::new(Vec::new());
+        run_traced(&mut context, &mut trace).unwrap();
+        assert_eq!(context.output(), &vec!(42));
+        assert_eq!(String::from_utf8(trace.finish().unwrap()).unwrap().lines().nth(1),
+                   Some("     2: SUB  #50 [9]=8 @1[11]<-42 rb=10"));
+
+        let mut context = Context::with_memory(vec!(10, 0, 0, 0, 99));
+        assert_eq!(context.resume(), Err(IntcodeError::UnknownOpcode { ip: 0, opcode: 10 }));
+    }
+
+    #[test]
+    fn test_override_and_remove() {
+        fn output_twice(operands: &mut Operands) -> Result<Flow, IntcodeError> {
+            let value = operands.read(0)?;
+            operands.output(value * 2)?;
+            Ok(Flow::Next)
+        }
+        let mut instructions = InstructionSet::standard();
+        let out = instructions.define(Instruction { implementation: output_twice, ..*instructions.get(4).unwrap() }).unwrap();
+        assert_eq!(out.map(|instruction| instruction.mnemonic), Some("OUT"));
+        let mut context = Context::with_memory(vec!(104, 21, 99));
+        context.set_instruction_set(instructions.clone());
+        assert_eq!(context.resume(), Ok(Status::Output(42)));
+
+        instructions.remove(99);
+        assert_eq!(instructions.iter().map(|instruction| instruction.opcode).collect::<Vec<usize>>(), (1..=9).collect::<Vec<usize>>());
+        let mut context = Context::new(vec!(104, 21, 99), (), Vec::new());
+        context.set_instruction_set(instructions);
+        assert_eq!(run(&mut context), Err(IntcodeError::UnknownOpcode { ip: 2, opcode: 99 }));
+    }
+
+    #[test]
+    fn test_definition_errors_and_duplicate_output() {
+        fn output_both(operands: &mut Operands) -> Result<Flow, IntcodeError> {
+            let (first, second) = (operands.read(0)?, operands.read(1)?);
+            operands.output(first)?;
+            operands.output(second)?;
+            Ok(Flow::Next)
+        }
+        let mut instructions = InstructionSet::standard();
+        assert_eq!(instructions.define(Instruction { opcode: 100, ..SUB }).unwrap_err(), DefinitionError::Opcode { mnemonic: "SUB", opcode: 100 });
+        assert_eq!(instructions.define(Instruction { operand_count: MAX_OPERANDS + 1, ..SUB }).unwrap_err(),
+                   DefinitionError::OperandCount { mnemonic: "SUB", operand_count: MAX_OPERANDS + 1 });
+        assert_eq!(instructions.define(Instruction { write_operand: Some(3), ..SUB }).unwrap_err(), DefinitionError::WriteOperand { mnemonic: "SUB" });
+        assert_eq!(instructions.define(Instruction { mnemonic: "add", ..SUB }).unwrap_err(), DefinitionError::Mnemonic { mnemonic: "add", opcode: 1 });
+        assert!(instructions.get(10).is_none());
+
+        instructions.define(Instruction { opcode: 11, mnemonic: "OUT2", operand_count: 2, write_operand: None, implementation: output_both }).unwrap();
+        let mut context = Context::with_memory(vec!(1111, 1, 2, 99));
+        context.set_instruction_set(instructions);
+        assert_eq!(context.resume(), Err(IntcodeError::DuplicateOutput { ip: 0, opcode: 1111 }));
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::sync::mpsc::{Receiver, Sender};
+
+/// Source of values for the input instruction.
+pub trait Input {
+    /// Returns the next value, or `None` when no value is available (now or ever).
+    fn read(&mut self) -> Option<i64>;
+}
+
+/// Sink for values produced by the output instruction.
+pub trait Output {
+    fn write(&mut self, value: i64) -> std::io::Result<()>;
+}
+
+impl Input for () {
+    fn read(&mut self) -> Option<i64> {
+        None
+    }
+}
+
+impl Output for () {
+    fn write(&mut self, _value: i64) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Input for Receiver<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.recv().ok()
+    }
+}
+
+impl Output for Sender<i64> {
+    fn write(&mut self, value: i64) -> std::io::Result<()> {
+        self.send(value).map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))
+    }
+}
+
+impl Input for VecDeque<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.pop_front()
+    }
+}
+
+impl Output for VecDeque<i64> {
+    fn write(&mut self, value: i64) -> std::io::Result<()> {
+        self.push_back(value);
+        Ok(())
+    }
+}
+
+impl Output for Vec<i64> {
+    fn write(&mut self, value: i64) -> std::io::Result<()> {
+        self.push(value);
+        Ok(())
+    }
+}
+
+impl<F: FnMut() -> Option<i64>> Input for F {
+    fn read(&mut self) -> Option<i64> {
+        self()
+    }
+}
+
+impl<F: FnMut(i64)> Output for F {
+    fn write(&mut self, value: i64) -> std::io::Result<()> {
+        self(value);
+        Ok(())
+    }
+}
+
+/// Reads one integer per line, skipping blank lines, until the end of input. A line that is
+/// not an integer is reported on stderr and skipped.
+impl Input for std::io::Stdin {
+    fn read(&mut self) -> Option<i64> {
+        read_integer(&mut self.lock(), &mut std::io::stderr(), "")
+    }
+}
+
+/// Reads an integer from the next line of `reader` that is not blank. A line that is not an
+/// integer is reported on `errors` and skipped. `prompt` is written to `errors` before every
+/// line is read. `None` at the end of input.
+pub fn read_integer<R: BufRead, W: Write>(reader: &mut R, errors: &mut W, prompt: &str) -> Option<i64> {
+    let mut line = String::new();
+    loop {
+        if !prompt.is_empty() {
+            let _ = write!(errors, "{}", prompt);
+            let _ = errors.flush();
+        }
+        line.clear();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let text = line.trim();
+        if !text.is_empty() {
+            match text.parse() {
+                Ok(value) => return Some(value),
+                Err(_) => {
+                    let _ = writeln!(errors, "invalid input {:?}, expected an integer", text);
+                }
+            }
+        }
+    }
+}
+
+/// Writes one integer per line.
+impl Output for std::io::Stdout {
+    fn write(&mut self, value: i64) -> std::io::Result<()> {
+        writeln!(self.lock(), "{}", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_integer_skips_invalid_lines() {
+        let mut reader = "\n 12 \ntwelve\n-3\n".as_bytes();
+        let mut errors = Vec::new();
+        assert_eq!(read_integer(&mut reader, &mut errors, ""), Some(12));
+        assert_eq!(read_integer(&mut reader, &mut errors, ""), Some(-3));
+        assert_eq!(read_integer(&mut reader, &mut errors, ""), None);
+        assert_eq!(String::from_utf8(errors).unwrap(), "invalid input \"twelve\", expected an integer\n");
+
+        let mut errors = Vec::new();
+        assert_eq!(read_integer(&mut "x\n\n5\n".as_bytes(), &mut errors, "> "), Some(5));
+        assert_eq!(String::from_utf8(errors).unwrap(), "> invalid input \"x\", expected an integer\n> > ");
+    }
+}
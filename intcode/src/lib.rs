@@ -1,271 +1,786 @@
-use std::collections::HashMap;
-use std::io::BufReader;
-use std::io::BufRead;
-use std::fs::File;
-use std::sync::mpsc::{Receiver, Sender};
-use std::thread;
-use std::sync::mpsc;
-
-
-struct Instruction {
-    opcode: usize,
-    operand_count: usize,
-    implementation: fn(Vec<Parameter>, &mut Context) -> IP,
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Instant;
+
+mod arithmetic;
+mod ascii;
+mod assembler;
+mod cfg;
+mod coverage;
+mod debugger;
+mod disassembler;
+mod execution;
+mod instruction_set;
+mod io;
+mod loader;
+mod memory;
+mod profile;
+mod scheduler;
+mod self_modification;
+mod snapshot;
+mod topology;
+mod trace;
+
+pub use arithmetic::ArithmeticPolicy;
+pub use ascii::{decode_ascii, encode_ascii, run_ascii, send_text, AsciiDecoder, AsciiInput, AsciiOutput, Text};
+pub use assembler::{assemble, assemble_with, AssemblyError};
+pub use cfg::{control_flow_graph, control_flow_graph_with, BasicBlock, ControlFlowGraph, Edge, EdgeCounts, EdgeKind};
+pub use coverage::{CellUse, Coverage};
+pub use debugger::{repl, Debugger, Stop};
+pub use disassembler::{decode, decode_with, disassemble, disassemble_with, listing, listing_with, Line, Mode, Operand};
+pub use execution::{execute, execute_with_limits, Execution, ExitStatus};
+pub use instruction_set::{DefinitionError, InstructionSet, Operands};
+pub use io::{read_integer, Input, Output};
+pub use loader::{load_program, load_program_file, parse_program, write_binary, LoadError};
+pub use memory::{AddressPolicy, Backend, Memory, MemoryConfig};
+pub use profile::Profiler;
+pub use scheduler::{MachineId, MachineState, NetworkStatus, Scheduler, SchedulerError};
+pub use self_modification::{SelfModification, SelfModificationMode};
+pub use snapshot::{Snapshot, SnapshotError};
+pub use topology::{Network, Topology};
+pub use trace::{JsonLinesTracer, TextTracer, TraceRecord, TracedParameter, Tracer};
+
+
+/// Definition of an opcode, see `InstructionSet` for defining opcodes of your own.
+#[derive(Clone, Copy)]
+pub struct Instruction {
+    /// The basic opcode, without parameter modes; below 100.
+    pub opcode: usize,
+    pub mnemonic: &'static str,
+    /// At most `MAX_OPERANDS`.
+    pub operand_count: usize,
+    /// Index of the operand the instruction writes to, if any. Used to trace the written
+    /// value and to detect self modification.
+    pub write_operand: Option<usize>,
+    pub implementation: fn(&mut Operands) -> Result<Flow, IntcodeError>,
 }
 
 impl std::fmt::Debug for Instruction {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        write!(fmt, "{{ opcode: {:#?}, operand_count: {} }}", self.opcode, self.operand_count)
+        write!(fmt, "{{ opcode: {:#?}, mnemonic: {}, operand_count: {} }}", self.opcode, self.mnemonic, self.operand_count)
     }
 }
 
-#[derive(std::fmt::Debug)]
-enum IP {
-    Relative(i64),
-    Absolute(usize),
+/// Where execution continues after an instruction.
+#[derive(std::fmt::Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Flow {
+    /// The instruction following the operands.
+    Next,
+    Jump(usize),
+    /// Input is needed that is not available yet. The instruction is executed again once
+    /// input arrives, so it must not have changed anything.
+    Wait,
     Halt,
 }
 
-#[derive(std::fmt::Debug)]
+#[derive(std::fmt::Debug, Clone, Copy)]
 enum Parameter {
-    Absolute(usize),
+    Absolute(i64),
     Immediate(i64),
     Relative(i64),
 }
 
+/// Reason a call to `Context::resume` returned control to the caller.
+#[derive(std::fmt::Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Status {
+    NeedsInput,
+    Output(i64),
+    Halted,
+}
+
+/// Execution limits of a context; `None` means unlimited. Only memory the program grows by
+/// accessing it is limited, not memory written through `Context::write`.
+#[derive(std::fmt::Debug, Clone, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub max_instructions: Option<u64>,
+    /// Maximum number of memory cells.
+    pub max_memory: Option<usize>,
+    /// Checked every `DEADLINE_INTERVAL` instructions.
+    pub deadline: Option<Instant>,
+}
+
+/// Number of instructions executed between two checks of the deadline.
+pub const DEADLINE_INTERVAL: u64 = 1024;
+
+/// The limit that stopped a program.
+#[derive(std::fmt::Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Limit {
+    Instructions(u64),
+    Memory(usize),
+    Deadline,
+}
+
+/// Fault raised while executing a program. Every variant carries the instruction pointer
+/// and the raw opcode (including parameter modes) of the failing instruction.
+#[derive(std::fmt::Debug, PartialEq, Eq, Clone)]
+pub enum IntcodeError {
+    UnknownOpcode { ip: usize, opcode: i64 },
+    ImmediateWrite { ip: usize, opcode: i64, parameter: usize },
+    NegativeAddress { ip: usize, opcode: i64, parameter: usize, address: i64 },
+    AddressOutOfRange { ip: usize, opcode: i64, parameter: usize, address: i64 },
+    NegativeJump { ip: usize, opcode: i64, parameter: usize, target: i64 },
+    InputClosed { ip: usize, opcode: i64 },
+    /// The output sink rejected a value; `ip` is the instruction following the output.
+    OutputClosed { ip: usize },
+    /// A limit was reached before executing the instruction at `ip`, after `executed` instructions.
+    LimitExceeded { ip: usize, limit: Limit, executed: u64 },
+    /// A result did not fit in a cell under `ArithmeticPolicy::Checked`.
+    Overflow { ip: usize, opcode: i64 },
+    /// Rejected in `SelfModificationMode::Strict`.
+    SelfModifying { ip: usize, opcode: i64, modification: SelfModification },
+    /// An instruction produced more than one output value.
+    DuplicateOutput { ip: usize, opcode: i64 },
+}
+
+impl std::fmt::Display for IntcodeError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            IntcodeError::UnknownOpcode { ip, opcode } =>
+                write!(fmt, "unknown opcode {} at {}", opcode, ip),
+            IntcodeError::ImmediateWrite { ip, opcode, parameter } =>
+                write!(fmt, "write in immediate mode to parameter {} of opcode {} at {}", parameter, opcode, ip),
+            IntcodeError::NegativeAddress { ip, opcode, parameter, address } =>
+                write!(fmt, "negative address {} in parameter {} of opcode {} at {}", address, parameter, opcode, ip),
+            IntcodeError::AddressOutOfRange { ip, opcode, parameter, address } =>
+                write!(fmt, "address {} out of range in parameter {} of opcode {} at {}", address, parameter, opcode, ip),
+            IntcodeError::NegativeJump { ip, opcode, parameter, target } =>
+                write!(fmt, "negative jump target {} in parameter {} of opcode {} at {}", target, parameter, opcode, ip),
+            IntcodeError::InputClosed { ip, opcode } =>
+                write!(fmt, "no input available for opcode {} at {}", opcode, ip),
+            IntcodeError::OutputClosed { ip } =>
+                write!(fmt, "output closed before {}", ip),
+            IntcodeError::LimitExceeded { ip, limit: Limit::Instructions(max), executed } =>
+                write!(fmt, "instruction limit of {} reached at {} after {} instructions", max, ip, executed),
+            IntcodeError::LimitExceeded { ip, limit: Limit::Memory(max), executed } =>
+                write!(fmt, "memory limit of {} cells exceeded at {} after {} instructions", max, ip, executed),
+            IntcodeError::LimitExceeded { ip, limit: Limit::Deadline, executed } =>
+                write!(fmt, "deadline passed at {} after {} instructions", ip, executed),
+            IntcodeError::Overflow { ip, opcode } =>
+                write!(fmt, "arithmetic overflow in opcode {} at {}", opcode, ip),
+            IntcodeError::SelfModifying { opcode, modification, .. } =>
+                write!(fmt, "self modification by opcode {}: {}", opcode, modification),
+            IntcodeError::DuplicateOutput { ip, opcode } =>
+                write!(fmt, "more than one output by opcode {} at {}", opcode, ip),
+        }
+    }
+}
+
+impl std::error::Error for IntcodeError {}
+
+/// A program together with its input source and output sink. The unit type `()` stands for
+/// "no source" and "no sink"; such a context is driven on the calling thread with `resume`.
 #[derive(std::fmt::Debug)]
-pub struct Context {
-    memory: Vec<i64>,
-    input: Receiver<i64>,
-    output: Sender<i64>,
-    relative_base: usize,
+pub struct Context<I = (), O = ()> {
+    machine: Machine,
+    input: I,
+    output: O,
+}
+
+#[derive(std::fmt::Debug, Clone)]
+struct Machine {
+    memory: Memory,
+    memory_config: MemoryConfig,
+    pending_input: VecDeque<i64>,
+    pending_output: Option<i64>,
+    relative_base: i64,
+    ip: usize,
+    /// Index of the parameter written by the instruction being executed, used when tracing.
+    written: Option<usize>,
+    /// Bit mask of the parameters read from memory by the instruction being executed, used
+    /// when tracing.
+    read_parameters: u8,
+    limits: Limits,
+    /// Number of instructions executed so far.
+    executed: u64,
+    self_modification: Option<Box<self_modification::Tracker>>,
+    arithmetic: ArithmeticPolicy,
+    /// `None` for the standard instruction set.
+    instructions: Option<Arc<InstructionSet>>,
 }
 
-fn calc_position_and_resize(context: &mut Context, parameter: &Parameter) -> usize {
-    let position = match parameter {
-        Parameter::Absolute(position) => *position,
-        Parameter::Relative(position) => (context.relative_base as i64 + *position) as usize,
+/// Position of the cell a parameter refers to, growing memory when needed. `None` means the
+/// address is outside the address space and reads as 0 (see `AddressPolicy::ZeroRead`).
+fn calc_position_and_resize(context: &mut Machine, parameters: &[Parameter], index: usize) -> Result<Option<usize>, IntcodeError> {
+    let address = match parameters[index] {
+        Parameter::Absolute(position) => position,
+        Parameter::Relative(position) => context.relative_address(position, index)?,
         Parameter::Immediate(_) => 0,
     };
-    if position >= context.memory.len() {
-        context.memory.resize(position + 1, 0);
+    if address >= 0 && (address as usize) < context.memory.len() {
+        return Ok(Some(address as usize));
     }
-    position
+    let position = context.locate(address, index)?;
+    if let Some(position) = position.filter(|&position| position >= context.memory.len()) {
+        if let Some(max) = context.limits.max_memory.filter(|&max| position >= max) {
+            return Err(context.limit_exceeded(Limit::Memory(max)));
+        }
+        context.memory.grow(position + 1);
+    }
+    Ok(position)
 }
 
 impl Context {
-    pub fn new(memory: Vec<i64>, input: Receiver<i64>, output: Sender<i64>) -> Context {
+    /// Creates a context without input source and output sink.
+    pub fn with_memory(memory: Vec<i64>) -> Context {
+        Context::new(memory, (), ())
+    }
+}
+
+impl<I: Input, O: Output> Context<I, O> {
+    pub fn new(memory: Vec<i64>, input: I, output: O) -> Context<I, O> {
         Context {
-            memory,
+            machine: Machine::new(memory),
             input,
             output,
-            relative_base: 0
         }
     }
 
+    pub fn push_input(&mut self, value: i64) {
+        self.machine.push_input(value);
+    }
+
+    /// Executes a single instruction. Returns a status when the instruction produced output,
+    /// needs input that neither the queue nor the input source can provide or halted the program.
+    pub fn step(&mut self) -> Result<Option<Status>, IntcodeError> {
+        self.step_with(None::<&mut Untraced>)
+    }
+
+    /// Like `step`, reporting the executed instruction to `tracer`.
+    pub fn step_traced(&mut self, tracer: &mut dyn Tracer) -> Result<Option<Status>, IntcodeError> {
+        self.step_with(Some(tracer))
+    }
+
+    fn step_with<T: Tracer + ?Sized>(&mut self, mut tracer: Option<&mut T>) -> Result<Option<Status>, IntcodeError> {
+        match self.machine.step(tracer.as_deref_mut())? {
+            Some(Status::NeedsInput) => match self.input.read() {
+                Some(value) => {
+                    self.machine.push_input(value);
+                    self.machine.step(tracer)
+                }
+                None => {
+                    if let Some(tracer) = tracer {
+                        tracer.wait(self.machine.ip);
+                    }
+                    Ok(Some(Status::NeedsInput))
+                }
+            },
+            status => Ok(status),
+        }
+    }
+
+    /// Runs until the program needs input, produces output or halts.
+    pub fn resume(&mut self) -> Result<Status, IntcodeError> {
+        self.resume_with(None::<&mut Untraced>)
+    }
+
+    /// Like `resume`, reporting every executed instruction to `tracer`.
+    pub fn resume_traced(&mut self, tracer: &mut dyn Tracer) -> Result<Status, IntcodeError> {
+        self.resume_with(Some(tracer))
+    }
+
+    fn resume_with<T: Tracer + ?Sized>(&mut self, mut tracer: Option<&mut T>) -> Result<Status, IntcodeError> {
+        loop {
+            if let Some(status) = self.step_with(tracer.as_deref_mut())? {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// Reads a memory cell. Panics when `position` lies beyond the end of memory.
     pub fn read(&mut self, position: usize) -> i64 {
-        self.memory[position]
+        match self.machine.memory.get(position) {
+            Some(value) => value,
+            None => panic!("read at {} beyond end of memory ({} cells)", position, self.machine.memory.len()),
+        }
     }
 
+    /// Writes a memory cell, growing memory when `position` lies beyond its end.
     pub fn write(&mut self, position: usize, value: i64) {
-        self.memory[position] = value;
+        if position >= self.machine.memory.len() {
+            self.machine.memory.grow(position + 1);
+        }
+        self.machine.write(position, value);
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.machine.memory
+    }
+
+    /// Sets the memory model, converting memory to the new backend.
+    pub fn set_memory_config(&mut self, config: MemoryConfig) {
+        let memory = std::mem::replace(&mut self.machine.memory, Memory::from(Vec::new()));
+        self.machine.memory = memory.with_backend(config.backend);
+        self.machine.memory_config = config;
     }
 
-    fn read_memory_parameter(&mut self, parameter: &Parameter) -> i64 {
-        let position= calc_position_and_resize(self, parameter);
-        match parameter {
-            Parameter::Absolute(_) | Parameter::Relative(_) => self.read(position),
-            Parameter::Immediate(value) => *value
-        }.clone()
+    pub fn memory_config(&self) -> &MemoryConfig {
+        &self.machine.memory_config
     }
 
-    fn write_memory_parameter(&mut self, parameter: &Parameter, value: i64) {
-        let position= calc_position_and_resize(self, parameter);
-        match parameter {
-            Parameter::Absolute(_) | Parameter::Relative(_) => self.write(position, value),
-            Parameter::Immediate(_) => panic!("Write in immediate mode is not possible!")
+    /// Duplicates the machine, connected to a new input source and output sink. Memory pages
+    /// are shared until either machine writes to them, so forking a large program is cheap.
+    pub fn fork<I2: Input, O2: Output>(&self, input: I2, output: O2) -> Context<I2, O2> {
+        Context { machine: self.machine.clone(), input, output }
+    }
+
+    /// Address of the next instruction to execute.
+    pub fn ip(&self) -> usize {
+        self.machine.ip
+    }
+
+    pub fn set_ip(&mut self, ip: usize) {
+        self.machine.ip = ip;
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.machine.relative_base
+    }
+
+    pub fn set_relative_base(&mut self, relative_base: i64) {
+        self.machine.relative_base = relative_base;
+    }
+
+    pub fn input_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    pub fn output(&self) -> &O {
+        &self.output
+    }
+
+    pub fn output_mut(&mut self) -> &mut O {
+        &mut self.output
+    }
+
+    /// Sets the limits checked while executing. The instruction limit counts from the start
+    /// of the program, not from this call.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.machine.limits = limits;
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.machine.limits
+    }
+
+    /// Number of instructions executed so far.
+    pub fn executed(&self) -> u64 {
+        self.machine.executed
+    }
+
+    pub fn into_output(self) -> O {
+        self.output
+    }
+}
+
+impl Machine {
+    fn new(memory: Vec<i64>) -> Machine {
+        Machine {
+            memory: Memory::from(memory),
+            memory_config: MemoryConfig::default(),
+            pending_input: VecDeque::new(),
+            pending_output: None,
+            relative_base: 0,
+            ip: 0,
+            written: None,
+            read_parameters: 0,
+            limits: Limits::default(),
+            executed: 0,
+            self_modification: None,
+            arithmetic: ArithmeticPolicy::default(),
+            instructions: None,
         }
     }
 
-    fn read_input(&mut self) -> i64 {
-        let i = self.input.recv().unwrap();
-//        println!("read {}", i);
-        i
+    fn push_input(&mut self, value: i64) {
+        self.pending_input.push_back(value);
     }
 
-    fn write_output(&mut self, value: i64) {
-//        println!("write {}", value);
-        self.output.send(value).unwrap();
+    fn step<T: Tracer + ?Sized>(&mut self, tracer: Option<&mut T>) -> Result<Option<Status>, IntcodeError> {
+        if let Some(max) = self.limits.max_instructions.filter(|&max| self.executed >= max) {
+            return Err(self.limit_exceeded(Limit::Instructions(max)));
+        }
+        if self.executed.is_multiple_of(DEADLINE_INTERVAL) && self.limits.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(self.limit_exceeded(Limit::Deadline));
+        }
+        let (&instruction, parameters) = parse_instruction(self.instruction_table(), self, self.ip)?;
+        let parameters = &parameters[..instruction.operand_count];
+        let check = match self.self_modification {
+            Some(_) => self.check_self_modification(&instruction, parameters)?,
+            None => None,
+        };
+        let record = tracer.as_ref().map(|_| {
+            self.written = None;
+            self.read_parameters = 0;
+            TraceRecord {
+                ip: self.ip,
+                opcode: self.opcode(),
+                mnemonic: instruction.mnemonic,
+                parameters: parameters.iter().map(|&parameter| self.resolve(parameter)).collect(),
+                relative_base: self.relative_base,
+            }
+        });
+
+        // An output of an instruction that waited or faulted is dropped.
+        self.pending_output = None;
+        let flow = (instruction.implementation)(&mut Operands { machine: self, parameters });
+        if let (Some(tracer), Some(mut record), Ok(next)) = (tracer, record, &flow) {
+            if *next != Flow::Wait {
+                for (index, parameter) in record.parameters.iter_mut().enumerate() {
+                    parameter.read = self.read_parameters & (1 << index) != 0;
+                }
+                if let Some(parameter) = self.written.and_then(|index| record.parameters.get_mut(index)) {
+                    parameter.write = true;
+                    parameter.value = parameter.address.map_or(0, |address| self.memory.read(address));
+                }
+                tracer.trace(&record);
+            }
+        }
+        let flow = flow?;
+        if flow != Flow::Wait {
+            self.executed += 1;
+            if let Some(check) = check {
+                self.apply_self_modification(check);
+            }
+        }
+        match flow {
+            Flow::Next => {
+                self.ip += 1 + parameters.len();
+            }
+            Flow::Jump(position) => {
+                self.ip = position;
+            }
+            Flow::Wait => {
+                return Ok(Some(Status::NeedsInput))
+            }
+            Flow::Halt => {
+                return Ok(Some(Status::Halted))
+            }
+        }
+        Ok(self.pending_output.take().map(Status::Output))
+    }
+
+    fn read(&mut self, position: usize) -> i64 {
+        self.memory.read(position)
+    }
+
+    /// Dispatch table of the instruction set the machine executes.
+    fn instruction_table(&self) -> &[Option<Instruction>; 100] {
+        self.instructions.as_ref().map_or(&INSTRUCTIONS, |set| &set.instructions)
+    }
+
+    fn opcode(&self) -> i64 {
+        self.memory.get(self.ip).unwrap_or(0)
+    }
+
+    fn write(&mut self, position: usize, value: i64) {
+        self.memory.write(position, value);
+    }
+
+    fn read_memory_parameter(&mut self, parameters: &[Parameter], index: usize) -> Result<i64, IntcodeError> {
+        Ok(match parameters[index] {
+            Parameter::Absolute(_) | Parameter::Relative(_) => {
+                match calc_position_and_resize(self, parameters, index)? {
+                    Some(position) => {
+                        self.read_parameters |= 1 << index;
+                        self.read(position)
+                    }
+                    None => 0,
+                }
+            }
+            Parameter::Immediate(value) => value
+        })
+    }
+
+    fn write_memory_parameter(&mut self, parameters: &[Parameter], index: usize, value: i64) -> Result<(), IntcodeError> {
+        match parameters[index] {
+            Parameter::Absolute(_) | Parameter::Relative(_) => {
+                if let Some(position) = calc_position_and_resize(self, parameters, index)? {
+                    self.write(position, value);
+                    self.written = Some(index);
+                }
+                Ok(())
+            }
+            Parameter::Immediate(_) => Err(IntcodeError::ImmediateWrite { ip: self.ip, opcode: self.opcode(), parameter: index })
+        }
+    }
+
+    /// Resolves a parameter for tracing without side effects: cells beyond the end of memory
+    /// read as 0 and negative addresses, which fault when accessed, resolve to no address.
+    fn resolve(&self, parameter: Parameter) -> TracedParameter {
+        let (mode, raw, address) = match parameter {
+            Parameter::Absolute(raw) => (Mode::Position, raw, Some(raw)),
+            Parameter::Immediate(raw) => (Mode::Immediate, raw, None),
+            Parameter::Relative(raw) => (Mode::Relative, raw, self.relative_base.checked_add(raw)),
+        };
+        let address = address.and_then(|address| self.locate(address, 0).ok().flatten());
+        let value = match (mode, address) {
+            (Mode::Immediate, _) => raw,
+            (_, Some(address)) => self.memory.get(address).unwrap_or(0),
+            (_, None) => 0,
+        };
+        TracedParameter { mode, raw, address, value, read: false, write: false }
+    }
+
+    /// Applies the address policies to an address outside the current memory, without growing it.
+    #[cold]
+    fn locate(&self, address: i64, parameter: usize) -> Result<Option<usize>, IntcodeError> {
+        let config = &self.memory_config;
+        let (policy, error) = if address < 0 {
+            (config.negative, IntcodeError::NegativeAddress { ip: self.ip, opcode: self.opcode(), parameter, address })
+        } else if config.address_limit.is_some_and(|limit| address as usize >= limit) {
+            (config.out_of_range, IntcodeError::AddressOutOfRange { ip: self.ip, opcode: self.opcode(), parameter, address })
+        } else {
+            return Ok(Some(address as usize));
+        };
+        match (policy, config.address_limit) {
+            (AddressPolicy::Wrap, Some(limit)) if limit > 0 => Ok(Some(address.rem_euclid(limit as i64) as usize)),
+            (AddressPolicy::ZeroRead, _) => Ok(None),
+            _ => Err(error),
+        }
+    }
+
+    /// Address of a parameter in relative mode. A sum beyond the range of `i64` faults
+    /// whatever the address and arithmetic policies.
+    #[inline]
+    fn relative_address(&self, offset: i64, parameter: usize) -> Result<i64, IntcodeError> {
+        self.relative_base.checked_add(offset).ok_or_else(|| self.address_overflow(offset, parameter))
+    }
+
+    #[cold]
+    fn address_overflow(&self, offset: i64, parameter: usize) -> IntcodeError {
+        let address = self.relative_base.saturating_add(offset);
+        if address < 0 {
+            IntcodeError::NegativeAddress { ip: self.ip, opcode: self.opcode(), parameter, address }
+        } else {
+            IntcodeError::AddressOutOfRange { ip: self.ip, opcode: self.opcode(), parameter, address }
+        }
+    }
+
+    #[cold]
+    fn limit_exceeded(&self, limit: Limit) -> IntcodeError {
+        IntcodeError::LimitExceeded { ip: self.ip, limit, executed: self.executed }
+    }
+
+    fn jump_target(&mut self, parameters: &[Parameter], index: usize) -> Result<usize, IntcodeError> {
+        let target = self.read_memory_parameter(parameters, index)?;
+        if target < 0 {
+            return Err(IntcodeError::NegativeJump { ip: self.ip, opcode: self.opcode(), parameter: index, target });
+        }
+        Ok(target as usize)
+    }
+
+    fn read_input(&mut self) -> Option<i64> {
+        self.pending_input.pop_front()
+    }
+
+    fn write_output(&mut self, value: i64) -> Result<(), IntcodeError> {
+        if self.pending_output.is_some() {
+            return Err(IntcodeError::DuplicateOutput { ip: self.ip, opcode: self.opcode() });
+        }
+        self.pending_output = Some(value);
+        Ok(())
     }
 }
 
-fn add_implementation(parameters: Vec<Parameter>, context: &mut Context) -> IP {
-    let value = context.read_memory_parameter(&parameters[0]) + context.read_memory_parameter(&parameters[1]);
-    context.write_memory_parameter(&parameters[2], value);
-    IP::Relative(4)
+fn add_implementation(operands: &mut Operands) -> Result<Flow, IntcodeError> {
+    let (a, b) = (operands.read(0)?, operands.read(1)?);
+    let value = operands.add(a, b)?;
+    operands.write(2, value)?;
+    Ok(Flow::Next)
 }
 
-fn multiply_implementation(parameters: Vec<Parameter>, context: &mut Context) -> IP  {
-    let value = context.read_memory_parameter(&parameters[0]) * context.read_memory_parameter(&parameters[1]);
-    context.write_memory_parameter(&parameters[2], value);
-    IP::Relative(4)
+fn multiply_implementation(operands: &mut Operands) -> Result<Flow, IntcodeError> {
+    let (a, b) = (operands.read(0)?, operands.read(1)?);
+    let value = operands.multiply(a, b)?;
+    operands.write(2, value)?;
+    Ok(Flow::Next)
 }
 
-fn input_implementation(parameters: Vec<Parameter>, context: &mut Context) -> IP  {
-    let value = context.read_input();
-//    println!("input: {}", value);
-    context.write_memory_parameter(&parameters[0], value);
-    IP::Relative(2)
+fn input_implementation(operands: &mut Operands) -> Result<Flow, IntcodeError> {
+    match operands.input() {
+        Some(value) => {
+            operands.write(0, value)?;
+            Ok(Flow::Next)
+        }
+        None => Ok(Flow::Wait)
+    }
 }
 
-fn output_implementation(parameters: Vec<Parameter>, context: &mut Context) -> IP  {
-    let value = context.read_memory_parameter(&parameters[0]);
-    context.write_output(value);
-    IP::Relative(2)
+fn output_implementation(operands: &mut Operands) -> Result<Flow, IntcodeError> {
+    let value = operands.read(0)?;
+    operands.output(value)?;
+    Ok(Flow::Next)
 }
 
-fn jump_not_zero_implementation(parameters: Vec<Parameter>, context: &mut Context) -> IP  {
-    if context.read_memory_parameter(&parameters[0]) != 0 {
-        IP::Absolute(context.read_memory_parameter(&parameters[1]) as usize)
+fn jump_not_zero_implementation(operands: &mut Operands) -> Result<Flow, IntcodeError> {
+    if operands.read(0)? != 0 {
+        Ok(Flow::Jump(operands.jump_target(1)?))
     } else {
-        IP::Relative(3)
+        Ok(Flow::Next)
     }
 }
 
-fn jump_zero_implementation(parameters: Vec<Parameter>, context: &mut Context) -> IP  {
-    let value = context.read_memory_parameter(&parameters[0]);
-    let position = context.read_memory_parameter(&parameters[1]);
-    if value == 0 {
-        IP::Absolute(position as usize)
+fn jump_zero_implementation(operands: &mut Operands) -> Result<Flow, IntcodeError> {
+    if operands.read(0)? == 0 {
+        Ok(Flow::Jump(operands.jump_target(1)?))
     } else {
-        IP::Relative(3)
+        Ok(Flow::Next)
     }
 }
 
-fn less_than_implementation(parameters: Vec<Parameter>, context: &mut Context) -> IP  {
-    let value = if context.read_memory_parameter(&parameters[0]) < context.read_memory_parameter(&parameters[1]) {
+fn less_than_implementation(operands: &mut Operands) -> Result<Flow, IntcodeError> {
+    let value = if operands.read(0)? < operands.read(1)? {
         1
     } else {
         0
     };
-    context.write_memory_parameter(&parameters[2], value);
-    IP::Relative(4)
+    operands.write(2, value)?;
+    Ok(Flow::Next)
 }
 
-fn equals_implementation(parameters: Vec<Parameter>, context: &mut Context) -> IP  {
-    let value = if context.read_memory_parameter(&parameters[0]) == context.read_memory_parameter(&parameters[1]) {
+fn equals_implementation(operands: &mut Operands) -> Result<Flow, IntcodeError> {
+    let value = if operands.read(0)? == operands.read(1)? {
         1
     } else {
         0
     };
-    context.write_memory_parameter(&parameters[2], value);
-    IP::Relative(4)
+    operands.write(2, value)?;
+    Ok(Flow::Next)
 }
 
-fn adjust_relative_base(parameters: Vec<Parameter>, context: &mut Context) -> IP  {
-//    println!("arb {:#?} {:#?}", context, parameters);
-    context.relative_base = (context.relative_base as i64 + context.read_memory_parameter(&parameters[0])) as usize;
-    IP::Relative(2)
+fn adjust_relative_base(operands: &mut Operands) -> Result<Flow, IntcodeError> {
+    let offset = operands.read(0)?;
+    operands.set_relative_base(operands.add(operands.relative_base(), offset)?);
+    Ok(Flow::Next)
 }
 
 
-fn halt_implementation(_parameters: Vec<Parameter>, _context: &mut Context) -> IP  {
-    IP::Halt
+fn halt_implementation(_operands: &mut Operands) -> Result<Flow, IntcodeError> {
+    Ok(Flow::Halt)
 }
 
-fn init_instruction_definitions() -> HashMap<usize, Instruction> {
-    let instructions = vec!(
-        Instruction { opcode: 1, operand_count: 3, implementation: add_implementation },
-        Instruction { opcode: 2, operand_count: 3, implementation: multiply_implementation },
-        Instruction { opcode: 3, operand_count: 1, implementation: input_implementation },
-        Instruction { opcode: 4, operand_count: 1, implementation: output_implementation },
-        Instruction { opcode: 5, operand_count: 2, implementation: jump_not_zero_implementation },
-        Instruction { opcode: 6, operand_count: 2, implementation: jump_zero_implementation },
-        Instruction { opcode: 7, operand_count: 3, implementation: less_than_implementation },
-        Instruction { opcode: 8, operand_count: 3, implementation: equals_implementation },
-        Instruction { opcode: 9, operand_count: 1, implementation: adjust_relative_base },
-        Instruction { opcode: 99, operand_count: 0, implementation: halt_implementation }
-    );
-    let mut result = HashMap::new();
-    for i in instructions {
-        result.insert(i.opcode, i);
-    }
+/// Largest operand count of an instruction.
+pub const MAX_OPERANDS: usize = 3;
+
+/// Dispatch table indexed by the basic opcode (the raw opcode modulo 100).
+static INSTRUCTIONS: [Option<Instruction>; 100] = init_instruction_definitions();
+
+const fn init_instruction_definitions() -> [Option<Instruction>; 100] {
+    const UNDEFINED: Option<Instruction> = None;
+    let mut result = [UNDEFINED; 100];
+    result[1] = Some(Instruction { opcode: 1, mnemonic: "ADD", operand_count: 3, write_operand: Some(2), implementation: add_implementation });
+    result[2] = Some(Instruction { opcode: 2, mnemonic: "MUL", operand_count: 3, write_operand: Some(2), implementation: multiply_implementation });
+    result[3] = Some(Instruction { opcode: 3, mnemonic: "IN", operand_count: 1, write_operand: Some(0), implementation: input_implementation });
+    result[4] = Some(Instruction { opcode: 4, mnemonic: "OUT", operand_count: 1, write_operand: None, implementation: output_implementation });
+    result[5] = Some(Instruction { opcode: 5, mnemonic: "JNZ", operand_count: 2, write_operand: None, implementation: jump_not_zero_implementation });
+    result[6] = Some(Instruction { opcode: 6, mnemonic: "JZ", operand_count: 2, write_operand: None, implementation: jump_zero_implementation });
+    result[7] = Some(Instruction { opcode: 7, mnemonic: "LT", operand_count: 3, write_operand: Some(2), implementation: less_than_implementation });
+    result[8] = Some(Instruction { opcode: 8, mnemonic: "EQ", operand_count: 3, write_operand: Some(2), implementation: equals_implementation });
+    result[9] = Some(Instruction { opcode: 9, mnemonic: "ARB", operand_count: 1, write_operand: None, implementation: adjust_relative_base });
+    result[99] = Some(Instruction { opcode: 99, mnemonic: "HLT", operand_count: 0, write_operand: None, implementation: halt_implementation });
     result
 }
 
-fn split_instruction(opcode: usize) -> (usize, Vec<usize>) {
-    let mut opcode = opcode;
-    let basic_opcode = opcode % 100;
-    opcode /= 100;
-    let mut modes = Vec::new();
+/// Parameter mode digits of an instruction (the opcode without its two lowest digits),
+/// read least significant digit first. Digits are computed on access, so decoding never allocates.
+#[derive(std::fmt::Debug, Clone, Copy)]
+struct Modes(usize);
+
+impl Modes {
+    /// Number of mode digits that are written out explicitly.
+    fn len(&self) -> usize {
+        let mut len = 0;
+        let mut modes = self.0;
+        while modes > 0 {
+            len += 1;
+            modes /= 10;
+        }
+        len
+    }
 
-    while opcode > 0 {
-        modes.push(opcode % 10);
-        opcode /= 10;
+    /// Mode of the parameter at `index`; digits that are not written out are 0 (position mode).
+    fn mode(&self, index: usize) -> u8 {
+        let digits = match index {
+            0 => self.0,
+            1 => self.0 / 10,
+            2 => self.0 / 100,
+            _ => self.0 / 10usize.saturating_pow(index as u32),
+        };
+        (digits % 10) as u8
     }
-    (basic_opcode, modes)
 }
 
-pub fn run(context: &mut Context) {
-    let instructions = init_instruction_definitions();
-    let mut offset: usize = 0;
+fn split_instruction(opcode: usize) -> (usize, Modes) {
+    (opcode % 100, Modes(opcode / 100))
+}
 
-    loop {
-        let (instruction, parameters) = parse_instruction(&instructions, context, offset);
+/// Tracer type of the untraced entry points, which always pass `None`. Keeping it a distinct
+/// type gives them their own instantiation of the execution loop without any tracing code.
+struct Untraced;
 
-        match (instruction.implementation)(parameters, context) {
-            IP::Relative(offset_change) => {
-                offset = (offset as i64 + offset_change) as usize;
-            }
-            IP::Absolute(position) => {
-                offset = position;
-            }
-            IP::Halt => {
-                break
-            }
-        }
-    }
-  //  println!("{:#?}", context);
+impl Tracer for Untraced {
+    fn trace(&mut self, _record: &TraceRecord) {}
 }
 
-pub fn run_input_output(opcodes: &Vec<i64>, inputs: &Vec<i64>) -> Vec<i64> {
-    let (input_send, input) = mpsc::channel();
-    let (output, output_recieve) = mpsc::channel();
-
-    inputs.iter().for_each(|&i| {input_send.send(i).unwrap();});
+/// Runs the program to completion, reading from the input source and writing to the output sink.
+pub fn run<I: Input, O: Output>(context: &mut Context<I, O>) -> Result<(), IntcodeError> {
+    run_with(context, None::<&mut Untraced>)
+}
 
-    let mut context = Context::new(opcodes.to_vec(), input, output);
-    thread::spawn(move || {
-        run(&mut context);
-    });
+/// Like `run`, reporting every executed instruction to `tracer`.
+pub fn run_traced<I: Input, O: Output>(context: &mut Context<I, O>, tracer: &mut dyn Tracer) -> Result<(), IntcodeError> {
+    run_with(context, Some(tracer))
+}
 
-    output_recieve.iter().collect()
+fn run_with<I: Input, O: Output, T: Tracer + ?Sized>(context: &mut Context<I, O>, mut tracer: Option<&mut T>) -> Result<(), IntcodeError> {
+    loop {
+        match context.resume_with(tracer.as_deref_mut())? {
+            Status::Output(value) => {
+                if context.output.write(value).is_err() {
+                    return Err(IntcodeError::OutputClosed { ip: context.machine.ip });
+                }
+            }
+            Status::NeedsInput => return Err(IntcodeError::InputClosed { ip: context.machine.ip, opcode: context.machine.opcode() }),
+            Status::Halted => break
+        }
+    }
+    Ok(())
 }
 
+pub fn run_input_output(opcodes: &[i64], inputs: &[i64]) -> Vec<i64> {
+    let mut context = Context::new(opcodes.to_vec(), inputs.iter().copied().collect::<VecDeque<_>>(), Vec::new());
+    run(&mut context).unwrap();
+    context.into_output()
+}
 
-fn parse_instruction<'a>(instructions: &'a HashMap<usize, Instruction>, context: &'a mut Context, offset: usize) -> (&'a Instruction, Vec<Parameter>) {
-    let opcode = context.memory[offset] as usize;
-    let (opcode, modes) = split_instruction(opcode);
-    let instruction = &instructions[&opcode];
-    let param_values:Vec<i64> = ((offset + 1)..(offset + 1 + instruction.operand_count)).map(|i| context.memory[i]).collect();
 
-    let parameters = param_values
-        .iter()
-        .enumerate()
-        .map(|(i, param)|
-            match modes.get(i) {
-                Some(1) => Parameter::Immediate(*param),
-                Some(2) => Parameter::Relative(*param),
-                _ => Parameter::Absolute(*param as usize) }
-        ).collect();
-    //println!("{:#?} {:#?}", instruction, parameters);
-    (instruction, parameters)
+/// Decodes the instruction at `offset` without allocating; the parameters are returned
+/// in a fixed size array of which the first `operand_count` entries are used.
+fn parse_instruction<'a>(instructions: &'a [Option<Instruction>; 100], context: &Machine, offset: usize) -> Result<(&'a Instruction, [Parameter; MAX_OPERANDS]), IntcodeError> {
+    let cells = context.memory.read_block::<{ MAX_OPERANDS + 1 }>(offset);
+    let raw_opcode = cells[0];
+    if raw_opcode < 0 {
+        return Err(IntcodeError::UnknownOpcode { ip: offset, opcode: raw_opcode });
+    }
+    let (opcode, modes) = split_instruction(raw_opcode as usize);
+    let instruction = instructions[opcode].as_ref()
+        .ok_or(IntcodeError::UnknownOpcode { ip: offset, opcode: raw_opcode })?;
+
+    let mut parameters = [Parameter::Immediate(0); MAX_OPERANDS];
+    for (i, parameter) in parameters.iter_mut().enumerate().take(instruction.operand_count) {
+        let param = cells[1 + i];
+        *parameter = match modes.mode(i) {
+            1 => Parameter::Immediate(param),
+            2 => Parameter::Relative(param),
+            _ => Parameter::Absolute(param)
+        };
+    }
+    Ok((instruction, parameters))
 }
 
+/// Loads a program with `load_program_file`, panicking when it cannot be loaded.
 pub fn read_program_from_file(path: &str) -> Vec<i64> {
-    let f = File::open(path).unwrap();
-    let file = BufReader::new(&f);
-    file.lines().next().unwrap().unwrap().split(",").map(|s| s.parse().unwrap()).collect()
+    load_program_file(path).unwrap_or_else(|error| panic!("cannot load {}: {}", path, error))
 }
 
 #[cfg(test)]
@@ -277,10 +792,180 @@ mod tests {
         let (opcode, modes) = split_instruction(1002);
         assert_eq!(opcode, 2);
         assert_eq!(modes.len(), 2);
-        assert_eq!(modes[0], 0);
-        assert_eq!(modes[1], 1);
+        assert_eq!(modes.mode(0), 0);
+        assert_eq!(modes.mode(1), 1);
+    }
+
+    #[test]
+    fn test_resume_stops_for_input_and_output() {
+        let mut context = Context::with_memory(vec!(3, 0, 4, 0, 99));
+        assert_eq!(context.resume(), Ok(Status::NeedsInput));
+        context.push_input(42);
+        assert_eq!(context.resume(), Ok(Status::Output(42)));
+        assert_eq!(context.resume(), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn test_resume_uses_queued_input() {
+        let mut context = Context::with_memory(vec!(3, 9, 3, 10, 1, 9, 10, 11, 99, 0, 0, 0));
+        context.push_input(2);
+        context.push_input(3);
+        assert_eq!(context.resume(), Ok(Status::Halted));
+        assert_eq!(context.read(11), 5);
     }
 
+    #[test]
+    fn test_unknown_opcode() {
+        let mut context = Context::with_memory(vec!(1101, 1, 1, 5, 42, 0));
+        assert_eq!(run(&mut context), Err(IntcodeError::UnknownOpcode { ip: 4, opcode: 42 }));
+    }
+
+    #[test]
+    fn test_immediate_write() {
+        let mut context = Context::with_memory(vec!(11101, 1, 1, 5, 99));
+        assert_eq!(run(&mut context), Err(IntcodeError::ImmediateWrite { ip: 0, opcode: 11101, parameter: 2 }));
+    }
+
+    #[test]
+    fn test_negative_relative_address() {
+        let mut context = Context::with_memory(vec!(109, -5, 204, 1, 99));
+        assert_eq!(run(&mut context), Err(IntcodeError::NegativeAddress { ip: 2, opcode: 204, parameter: 0, address: -4 }));
+    }
+
+    #[test]
+    fn test_negative_jump() {
+        let mut context = Context::with_memory(vec!(1105, 1, -1));
+        assert_eq!(run(&mut context), Err(IntcodeError::NegativeJump { ip: 0, opcode: 1105, parameter: 1, target: -1 }));
+
+        // Jumps that are not taken never look at their target.
+        assert_eq!(run(&mut Context::with_memory(vec!(1106, 1, -5, 99))), Ok(()));
+        assert_eq!(run(&mut Context::with_memory(vec!(1105, 0, -5, 99))), Ok(()));
+    }
+
+    #[test]
+    fn test_relative_address_overflow() {
+        let mut context = Context::with_memory(vec!(109, i64::MAX, 204, 1, 99));
+        assert_eq!(run(&mut context), Err(IntcodeError::AddressOutOfRange { ip: 2, opcode: 204, parameter: 0, address: i64::MAX }));
+        let mut context = Context::with_memory(vec!(109, i64::MIN, 204, -1, 99));
+        assert_eq!(run(&mut context), Err(IntcodeError::NegativeAddress { ip: 2, opcode: 204, parameter: 0, address: i64::MIN }));
+    }
+
+    #[test]
+    fn test_input_closed() {
+        let (sender, input) = std::sync::mpsc::channel();
+        let (output, _) = std::sync::mpsc::channel();
+        drop(sender);
+        let mut context = Context::new(vec!(3, 0, 99), input, output);
+        assert_eq!(run(&mut context), Err(IntcodeError::InputClosed { ip: 0, opcode: 3 }));
+    }
+
+    #[test]
+    fn test_output_closed() {
+        let (_, input) = std::sync::mpsc::channel();
+        let (output, receiver) = std::sync::mpsc::channel();
+        drop(receiver);
+        let mut context = Context::new(vec!(104, 1, 99), input, output);
+        assert_eq!(run(&mut context), Err(IntcodeError::OutputClosed { ip: 2 }));
+    }
+
+    #[test]
+    fn test_buffer_input_output() {
+        let mut context = Context::new(vec!(3, 0, 3, 1, 4, 1, 4, 0, 99), VecDeque::from(vec!(7, 8)), Vec::new());
+        run(&mut context).unwrap();
+        assert_eq!(context.output(), &vec!(8, 7));
+    }
+
+    #[test]
+    fn test_closure_input_output() {
+        let mut next = 0;
+        let mut total = 0;
+        let mut context = Context::new(vec!(3, 0, 4, 0, 3, 0, 4, 0, 99), || { next += 5; Some(next) }, |value| total += value);
+        run(&mut context).unwrap();
+        drop(context);
+        assert_eq!(total, 15);
+    }
+
+    #[test]
+    fn test_source_refills_after_queue() {
+        let mut context = Context::new(vec!(3, 0, 3, 1, 99), VecDeque::from(vec!(2)), ());
+        context.push_input(1);
+        assert_eq!(context.resume(), Ok(Status::Halted));
+        assert_eq!((context.read(0), context.read(1)), (1, 2));
+    }
+
+    #[test]
+    fn test_instruction_limit() {
+        let mut context = Context::with_memory(vec!(1101, 1, 2, 5, 1105, 1, 4));
+        context.set_limits(Limits { max_instructions: Some(10), ..Limits::default() });
+        assert_eq!(context.resume(), Err(IntcodeError::LimitExceeded { ip: 4, limit: Limit::Instructions(10), executed: 10 }));
+        assert_eq!(context.executed(), 10);
+    }
+
+    #[test]
+    fn test_memory_and_deadline_limits() {
+        let mut context = Context::with_memory(vec!(1101, 1, 2, 1000, 99));
+        context.set_limits(Limits { max_memory: Some(1000), ..Limits::default() });
+        assert_eq!(context.resume(), Err(IntcodeError::LimitExceeded { ip: 0, limit: Limit::Memory(1000), executed: 0 }));
+
+        context.set_limits(Limits { deadline: Some(Instant::now()), ..Limits::default() });
+        assert_eq!(context.resume(), Err(IntcodeError::LimitExceeded { ip: 0, limit: Limit::Deadline, executed: 0 }));
+    }
+
+    #[test]
+    fn test_address_policies() {
+        // Adds the cells at -1 and 10 and writes the sum to 13.
+        let program = vec!(1, -1, 10, 13, 99);
+        let mut context = Context::with_memory(program.clone());
+        assert_eq!(context.resume(), Err(IntcodeError::NegativeAddress { ip: 0, opcode: 1, parameter: 0, address: -1 }));
+
+        let mut context = Context::with_memory(program.clone());
+        context.set_memory_config(MemoryConfig { address_limit: Some(8), ..MemoryConfig::default() });
+        assert_eq!(context.resume(), Err(IntcodeError::NegativeAddress { ip: 0, opcode: 1, parameter: 0, address: -1 }));
+
+        let mut context = Context::with_memory(program.clone());
+        context.set_memory_config(MemoryConfig { address_limit: Some(8), negative: AddressPolicy::ZeroRead, ..MemoryConfig::default() });
+        assert_eq!(context.resume(), Err(IntcodeError::AddressOutOfRange { ip: 0, opcode: 1, parameter: 1, address: 10 }));
+
+        let mut context = Context::with_memory(program.clone());
+        context.set_memory_config(MemoryConfig {
+            address_limit: Some(8), out_of_range: AddressPolicy::Wrap, negative: AddressPolicy::Wrap, ..MemoryConfig::default()
+        });
+        assert_eq!(context.resume(), Ok(Status::Halted));
+        assert_eq!(context.memory().to_vec(), vec!(1, -1, 10, 13, 99, 10, 0, 0));
+        assert_eq!(context.read(4), 99);
+
+        let mut context = Context::with_memory(program);
+        context.set_memory_config(MemoryConfig {
+            address_limit: Some(8), out_of_range: AddressPolicy::ZeroRead, negative: AddressPolicy::ZeroRead, ..MemoryConfig::default()
+        });
+        assert_eq!(context.resume(), Ok(Status::Halted));
+        assert_eq!(context.memory().len(), 5);
+    }
+
+    #[test]
+    fn test_sparse_memory_at_large_address() {
+        let mut context = Context::with_memory(vec!(1101, 3, 4, 1_000_000_000_000, 4, 1_000_000_000_000, 99));
+        context.set_memory_config(MemoryConfig { backend: Backend::Sparse, ..MemoryConfig::default() });
+        assert_eq!(context.resume(), Ok(Status::Output(7)));
+        assert_eq!(context.memory().len(), 1_000_000_000_001);
+        assert_eq!(context.memory().allocated(), 512);
+    }
+
+    #[test]
+    fn test_fork_runs_independently() {
+        // Reads a value into cell 0, doubles it and outputs it.
+        let mut context = Context::with_memory(vec!(3, 0, 1002, 0, 2, 0, 4, 0, 99));
+        assert_eq!(context.resume(), Ok(Status::NeedsInput));
+        let mut forks: Vec<Context<VecDeque<i64>, Vec<i64>>> = (1..=3)
+            .map(|value| context.fork(VecDeque::from(vec!(value)), Vec::new()))
+            .collect();
+        for fork in forks.iter_mut() {
+            run(fork).unwrap();
+        }
+        let outputs: Vec<Vec<i64>> = forks.into_iter().map(|fork| fork.into_output()).collect();
+        assert_eq!(outputs, vec!(vec!(2), vec!(4), vec!(6)));
+        assert_eq!(context.memory().to_vec(), vec!(3, 0, 1002, 0, 2, 0, 4, 0, 99));
+    }
 
 
 }
@@ -0,0 +1,185 @@
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// First bytes of a program in binary form. The header is followed by the number of cells and
+/// the cells, all as zigzag encoded LEB128 variable length integers.
+const BINARY_MAGIC: &[u8; 4] = b"ICB\x01";
+
+#[derive(std::fmt::Debug, PartialEq, Eq, Clone)]
+pub enum LoadError {
+    Io { kind: std::io::ErrorKind, message: String },
+    /// The source contains no values.
+    Empty,
+    /// Lines and columns count from 1.
+    Syntax { line: usize, column: usize, text: String },
+    MissingValue { line: usize, column: usize },
+    Binary { offset: usize, message: &'static str },
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            LoadError::Io { message, .. } =>
+                write!(fmt, "{}", message),
+            LoadError::Empty =>
+                write!(fmt, "program is empty"),
+            LoadError::Syntax { line, column, text } =>
+                write!(fmt, "line {}, column {}: invalid value {:?}", line, column, text),
+            LoadError::MissingValue { line, column } =>
+                write!(fmt, "line {}, column {}: missing value", line, column),
+            LoadError::Binary { offset, message } =>
+                write!(fmt, "binary program, byte {}: {}", offset, message),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<std::io::Error> for LoadError {
+    fn from(error: std::io::Error) -> LoadError {
+        LoadError::Io { kind: error.kind(), message: error.to_string() }
+    }
+}
+
+/// Parses a program in text form: values separated by commas, over as many lines as needed.
+/// Whitespace around values, blank lines, a comma at the end of a line and comments from `;`
+/// to the end of the line are allowed.
+pub fn parse_program(text: &str) -> Result<Vec<i64>, LoadError> {
+    let mut program = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let code = line.find(';').map_or(line, |comment| &line[..comment]);
+        let fields: Vec<&str> = code.split(',').collect();
+        let mut column = 1;
+        for (field_index, field) in fields.iter().enumerate() {
+            let value = field.trim();
+            let start = column + field.chars().count() - field.trim_start().chars().count();
+            if value.is_empty() {
+                if field_index + 1 < fields.len() {
+                    return Err(LoadError::MissingValue { line: index + 1, column: start });
+                }
+            } else {
+                match value.parse() {
+                    Ok(value) => program.push(value),
+                    Err(_) => return Err(LoadError::Syntax { line: index + 1, column: start, text: value.to_string() }),
+                }
+            }
+            column += field.chars().count() + 1;
+        }
+    }
+    if program.is_empty() {
+        return Err(LoadError::Empty);
+    }
+    Ok(program)
+}
+
+/// Loads a program in text or binary form from any source, such as a file or stdin.
+pub fn load_program<R: Read>(mut reader: R) -> Result<Vec<i64>, LoadError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if bytes.starts_with(BINARY_MAGIC) {
+        return decode_binary(&bytes);
+    }
+    let text = String::from_utf8(bytes)
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+    parse_program(&text)
+}
+
+pub fn load_program_file<P: AsRef<Path>>(path: P) -> Result<Vec<i64>, LoadError> {
+    load_program(std::fs::File::open(path)?)
+}
+
+/// Writes a program in the binary form read by `load_program`.
+pub fn write_binary<W: Write>(program: &[i64], mut writer: W) -> std::io::Result<()> {
+    let mut bytes = BINARY_MAGIC.to_vec();
+    write_varint(&mut bytes, program.len() as u64);
+    for &value in program {
+        write_varint(&mut bytes, ((value << 1) ^ (value >> 63)) as u64);
+    }
+    writer.write_all(&bytes)
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], offset: &mut usize) -> Result<u64, LoadError> {
+    let start = *offset;
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = match bytes.get(*offset) {
+            Some(&byte) => byte,
+            None => return Err(LoadError::Binary { offset: start, message: "unexpected end of data" }),
+        };
+        *offset += 1;
+        // The tenth byte holds only the top bit.
+        if shift == 63 && byte & 0x7f > 1 {
+            return Err(LoadError::Binary { offset: start, message: "value longer than 64 bits" });
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(LoadError::Binary { offset: start, message: "value longer than 64 bits" })
+}
+
+fn decode_binary(bytes: &[u8]) -> Result<Vec<i64>, LoadError> {
+    let mut offset = BINARY_MAGIC.len();
+    let count = read_varint(bytes, &mut offset)? as usize;
+    // Every cell takes at least a byte, so a count beyond that is corrupt.
+    if count > bytes.len() - offset {
+        return Err(LoadError::Binary { offset: BINARY_MAGIC.len(), message: "more cells than data" });
+    }
+    let mut program = Vec::with_capacity(count);
+    for _ in 0..count {
+        let value = read_varint(bytes, &mut offset)?;
+        program.push((value >> 1) as i64 ^ -((value & 1) as i64));
+    }
+    if offset != bytes.len() {
+        return Err(LoadError::Binary { offset, message: "data after the last cell" });
+    }
+    if program.is_empty() {
+        return Err(LoadError::Empty);
+    }
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_text() {
+        assert_eq!(parse_program("1,9,10,3,\r\n  2, 3 ,11,0 ; add and multiply\n\n99,\n30,40,50\n"),
+                   Ok(vec!(1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50)));
+        assert_eq!(parse_program("1,2\n3, 4 5"), Err(LoadError::Syntax { line: 2, column: 4, text: "4 5".to_string() }));
+        assert_eq!(parse_program("1,,2"), Err(LoadError::MissingValue { line: 1, column: 3 }));
+        assert_eq!(parse_program("\n ; nothing\n"), Err(LoadError::Empty));
+        assert_eq!(load_program("1,x".as_bytes()).unwrap_err().to_string(), "line 1, column 3: invalid value \"x\"");
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let program = vec!(1, -1, 0, 64, -65, i64::MAX, i64::MIN, 99);
+        let mut bytes = Vec::new();
+        write_binary(&program, &mut bytes).unwrap();
+        assert_eq!(bytes.len(), 4 + 1 + 1 + 1 + 1 + 2 + 2 + 10 + 10 + 2);
+        assert_eq!(load_program(bytes.as_slice()), Ok(program));
+        assert_eq!(load_program(&bytes[..bytes.len() - 1]), Err(LoadError::Binary { offset: 32, message: "unexpected end of data" }));
+    }
+
+    #[test]
+    fn test_binary_value_too_long() {
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.push(1);
+        bytes.extend_from_slice(&[0xff; 9]);
+        bytes.push(0x02);
+        assert_eq!(load_program(bytes.as_slice()), Err(LoadError::Binary { offset: 5, message: "value longer than 64 bits" }));
+        *bytes.last_mut().unwrap() = 0x01;
+        assert_eq!(load_program(bytes.as_slice()), Ok(vec!(i64::MIN)));
+    }
+}
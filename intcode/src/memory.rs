@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const PAGE_SIZE: usize = 256;
+
+type Page = [i64; PAGE_SIZE];
+
+/// How memory pages are stored.
+#[derive(std::fmt::Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Every page up to the end of memory is allocated; fastest for ordinary programs.
+    Dense,
+    /// Only pages that were written are allocated, the rest read as 0. Accessing a large
+    /// address costs a single page instead of all memory below it.
+    Sparse,
+}
+
+/// What an access to an address outside the address space does.
+#[derive(std::fmt::Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressPolicy {
+    /// Fault with `IntcodeError::NegativeAddress` or `IntcodeError::AddressOutOfRange`.
+    Error,
+    /// Take the address modulo the address limit. Without a limit this behaves as `Error`.
+    Wrap,
+    /// Reads return 0 and writes are ignored.
+    ZeroRead,
+}
+
+/// Memory model of a machine. The address limit bounds the addresses a program can grow
+/// memory to; without one, any non-negative address is valid.
+#[derive(std::fmt::Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryConfig {
+    pub backend: Backend,
+    pub address_limit: Option<usize>,
+    /// Applies to addresses at or beyond the address limit.
+    pub out_of_range: AddressPolicy,
+    pub negative: AddressPolicy,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> MemoryConfig {
+        MemoryConfig { backend: Backend::Dense, address_limit: None, out_of_range: AddressPolicy::Error, negative: AddressPolicy::Error }
+    }
+}
+
+#[derive(std::fmt::Debug, Clone)]
+enum Storage {
+    Dense(Vec<Arc<Page>>),
+    Sparse(HashMap<usize, Arc<Page>>),
+}
+
+/// Memory of a machine, stored in fixed size pages that are shared between forks and copied
+/// on the first write. Cloning is cheap: it copies one pointer per page.
+#[derive(std::fmt::Debug, Clone)]
+pub struct Memory {
+    storage: Storage,
+    len: usize,
+}
+
+impl Memory {
+    /// Number of cells, including those the program grew memory to by accessing them.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn backend(&self) -> Backend {
+        match self.storage {
+            Storage::Dense(_) => Backend::Dense,
+            Storage::Sparse(_) => Backend::Sparse,
+        }
+    }
+
+    /// Number of cells actually allocated, a multiple of the page size.
+    pub fn allocated(&self) -> usize {
+        match &self.storage {
+            Storage::Dense(pages) => pages.len() * PAGE_SIZE,
+            Storage::Sparse(pages) => pages.len() * PAGE_SIZE,
+        }
+    }
+
+    pub fn get(&self, position: usize) -> Option<i64> {
+        if position < self.len {
+            Some(self.read(position))
+        } else {
+            None
+        }
+    }
+
+    /// All cells in order. For sparse memory this includes every unallocated cell.
+    pub fn iter(&self) -> impl Iterator<Item = i64> + '_ {
+        (0..self.len).map(move |position| self.read(position))
+    }
+
+    pub fn to_vec(&self) -> Vec<i64> {
+        self.iter().collect()
+    }
+
+    /// Runs of consecutive cells in order of address, without zeros at either end. Cells
+    /// outside the runs are 0. Only allocated pages are visited, so this is cheap for sparse
+    /// memory however large it is.
+    pub(crate) fn runs(&self) -> Vec<(usize, Vec<i64>)> {
+        let mut runs: Vec<(usize, Vec<i64>)> = Vec::new();
+        for page in self.pages() {
+            let start = page * PAGE_SIZE;
+            let cells = (start..(start + PAGE_SIZE).min(self.len)).map(|position| self.read(position));
+            match runs.last_mut() {
+                Some((address, run)) if *address + run.len() == start => run.extend(cells),
+                _ => runs.push((start, cells.collect())),
+            }
+        }
+        runs.into_iter().filter_map(|(address, run)| {
+            let first = run.iter().position(|&value| value != 0)?;
+            let last = run.iter().rposition(|&value| value != 0)?;
+            Some((address + first, run[first..=last].to_vec()))
+        }).collect()
+    }
+
+    /// Indices of the allocated pages, in order.
+    fn pages(&self) -> Vec<usize> {
+        match &self.storage {
+            Storage::Dense(pages) => (0..pages.len()).collect(),
+            Storage::Sparse(pages) => {
+                let mut indices: Vec<usize> = pages.keys().copied().collect();
+                indices.sort_unstable();
+                indices
+            }
+        }
+    }
+
+    /// The same contents stored in `backend`. Converting to dense allocates all memory.
+    pub(crate) fn with_backend(self, backend: Backend) -> Memory {
+        let storage = match (self.storage, backend) {
+            (Storage::Dense(pages), Backend::Sparse) => Storage::Sparse(pages.into_iter().enumerate().collect()),
+            (Storage::Sparse(mut sparse), Backend::Dense) => {
+                let pages = (0..self.len.div_ceil(PAGE_SIZE))
+                    .map(|page| sparse.remove(&page).unwrap_or_else(|| Arc::new([0; PAGE_SIZE])))
+                    .collect();
+                Storage::Dense(pages)
+            }
+            (storage, _) => storage,
+        };
+        Memory { storage, len: self.len }
+    }
+
+    /// Reads `N` consecutive cells starting at `position`, cells beyond the end read as 0.
+    pub(crate) fn read_block<const N: usize>(&self, position: usize) -> [i64; N] {
+        let offset = position % PAGE_SIZE;
+        let mut block = [0; N];
+        if let Storage::Dense(pages) = &self.storage {
+            if offset + N <= PAGE_SIZE && position + N <= self.len {
+                block.copy_from_slice(&pages[position / PAGE_SIZE][offset..offset + N]);
+                return block;
+            }
+        }
+        for (i, cell) in block.iter_mut().enumerate() {
+            *cell = self.get(position + i).unwrap_or(0);
+        }
+        block
+    }
+
+    /// Reads a cell within `len`.
+    pub(crate) fn read(&self, position: usize) -> i64 {
+        match &self.storage {
+            Storage::Dense(pages) => pages[position / PAGE_SIZE][position % PAGE_SIZE],
+            Storage::Sparse(pages) => read_sparse(pages, position),
+        }
+    }
+
+    /// Writes a cell within `len`, copying its page first when it is shared with a fork.
+    pub(crate) fn write(&mut self, position: usize, value: i64) {
+        match &mut self.storage {
+            Storage::Dense(pages) => Arc::make_mut(&mut pages[position / PAGE_SIZE])[position % PAGE_SIZE] = value,
+            Storage::Sparse(pages) => write_sparse(pages, position, value),
+        }
+    }
+
+    /// Grows memory to `len` cells, filling new cells with 0.
+    pub(crate) fn grow(&mut self, len: usize) {
+        if let Storage::Dense(pages) = &mut self.storage {
+            while pages.len() * PAGE_SIZE < len {
+                pages.push(Arc::new([0; PAGE_SIZE]));
+            }
+        }
+        self.len = self.len.max(len);
+    }
+}
+
+// Kept out of line so the dense paths stay small enough to inline into the interpreter.
+#[inline(never)]
+fn read_sparse(pages: &HashMap<usize, Arc<Page>>, position: usize) -> i64 {
+    pages.get(&(position / PAGE_SIZE)).map_or(0, |page| page[position % PAGE_SIZE])
+}
+
+#[inline(never)]
+fn write_sparse(pages: &mut HashMap<usize, Arc<Page>>, position: usize, value: i64) {
+    Arc::make_mut(pages.entry(position / PAGE_SIZE).or_insert_with(|| Arc::new([0; PAGE_SIZE])))[position % PAGE_SIZE] = value;
+}
+
+impl From<Vec<i64>> for Memory {
+    fn from(cells: Vec<i64>) -> Memory {
+        let pages = cells.chunks(PAGE_SIZE).map(|chunk| {
+            let mut page = [0; PAGE_SIZE];
+            page[..chunk.len()].copy_from_slice(chunk);
+            Arc::new(page)
+        }).collect();
+        Memory { storage: Storage::Dense(pages), len: cells.len() }
+    }
+}
+
+/// Memories are equal when their cells are, whatever the backend.
+impl PartialEq for Memory {
+    fn eq(&self, other: &Memory) -> bool {
+        if self.len != other.len {
+            return false;
+        }
+        // Cells of pages allocated by neither memory are 0 in both.
+        let mut pages = self.pages();
+        pages.extend(other.pages());
+        pages.sort_unstable();
+        pages.dedup();
+        pages.into_iter().all(|page| {
+            (page * PAGE_SIZE..((page + 1) * PAGE_SIZE).min(self.len)).all(|position| self.read(position) == other.read(position))
+        })
+    }
+}
+
+impl Eq for Memory {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pages_are_copied_on_write() {
+        let mut memory = Memory::from((0..600).collect::<Vec<i64>>());
+        let fork = memory.clone();
+        memory.write(300, -1);
+        memory.grow(1000);
+        if let (Storage::Dense(pages), Storage::Dense(fork_pages)) = (&memory.storage, &fork.storage) {
+            assert!(Arc::ptr_eq(&pages[0], &fork_pages[0]));
+            assert!(!Arc::ptr_eq(&pages[1], &fork_pages[1]));
+        }
+        assert_eq!((memory.get(300), fork.get(300)), (Some(-1), Some(300)));
+        assert_eq!((memory.len(), fork.len()), (1000, 600));
+        assert_eq!((memory.get(999), fork.get(600)), (Some(0), None));
+        assert_eq!(fork.to_vec(), (0..600).collect::<Vec<i64>>());
+    }
+
+    #[test]
+    fn test_sparse_allocates_written_pages() {
+        let mut memory = Memory::from(vec!(1, 2, 3)).with_backend(Backend::Sparse);
+        memory.grow(1_000_000_001);
+        memory.write(1_000_000_000, 7);
+        assert_eq!(memory.allocated(), 2 * PAGE_SIZE);
+        assert_eq!((memory.get(2), memory.get(500_000), memory.get(1_000_000_000)), (Some(3), Some(0), Some(7)));
+
+        let mut memory = Memory::from(vec!(1, 2, 3)).with_backend(Backend::Sparse);
+        memory.grow(PAGE_SIZE * 3);
+        memory.write(PAGE_SIZE * 2, 4);
+        let dense = memory.clone().with_backend(Backend::Dense);
+        assert_eq!((dense.backend(), dense.allocated()), (Backend::Dense, PAGE_SIZE * 3));
+        assert_eq!(dense, memory);
+        assert_eq!(memory.runs(), vec!((0, vec!(1, 2, 3)), (PAGE_SIZE * 2, vec!(4))));
+        assert_eq!(dense.runs(), vec!((0, [vec!(1, 2, 3), vec!(0; PAGE_SIZE * 2 - 3), vec!(4)].concat())));
+    }
+}
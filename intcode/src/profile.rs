@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::trace::json_string;
+use super::{Mode, TraceRecord, Tracer};
+
+const MODES: [Mode; 3] = [Mode::Position, Mode::Immediate, Mode::Relative];
+
+/// Counts executions per opcode, instruction address and parameter mode, memory reads and
+/// writes per address and input waits. Pass it to `run_traced`, `Context::resume_traced` or
+/// `Context::step_traced`; profiles of several runs accumulate.
+#[derive(std::fmt::Debug, Clone, Default)]
+pub struct Profiler {
+    instructions: u64,
+    input_waits: u64,
+    opcodes: HashMap<&'static str, u64>,
+    addresses: HashMap<usize, (&'static str, u64)>,
+    modes: [u64; 3],
+    reads: HashMap<usize, u64>,
+    writes: HashMap<usize, u64>,
+}
+
+impl Tracer for Profiler {
+    fn trace(&mut self, record: &TraceRecord) {
+        self.instructions += 1;
+        *self.opcodes.entry(record.mnemonic).or_insert(0) += 1;
+        self.addresses.entry(record.ip).or_insert((record.mnemonic, 0)).1 += 1;
+        for parameter in &record.parameters {
+            self.modes[parameter.mode.digit() as usize] += 1;
+            if let Some(address) = parameter.address {
+                if parameter.read {
+                    *self.reads.entry(address).or_insert(0) += 1;
+                }
+                if parameter.write {
+                    *self.writes.entry(address).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    fn wait(&mut self, _ip: usize) {
+        self.input_waits += 1;
+    }
+}
+
+/// Entries ordered by count, highest first, and by key for equal counts.
+fn ranked<K: Copy + Ord>(counts: impl Iterator<Item = (K, u64)>) -> Vec<(K, u64)> {
+    let mut counts: Vec<(K, u64)> = counts.collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts
+}
+
+fn section(table: &mut String, title: &str, total: u64, rows: impl Iterator<Item = (String, u64)>) {
+    let _ = write!(table, "\n{:<12} {:>12} {:>7}\n", title, "count", "share");
+    for (name, count) in rows {
+        let share = if total == 0 { 0.0 } else { count as f64 * 100.0 / total as f64 };
+        let _ = writeln!(table, "{:<12} {:>12} {:>6.1}%", name, count, share);
+    }
+}
+
+fn json_object<K: std::fmt::Display>(entries: impl Iterator<Item = (K, u64)>) -> String {
+    let entries: Vec<String> = entries.map(|(key, count)| format!("{}:{}", json_string(&key.to_string()), count)).collect();
+    format!("{{{}}}", entries.join(","))
+}
+
+fn by_address(counts: &HashMap<usize, u64>) -> Vec<(usize, u64)> {
+    let mut counts: Vec<(usize, u64)> = counts.iter().map(|(&address, &count)| (address, count)).collect();
+    counts.sort_unstable();
+    counts
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn input_waits(&self) -> u64 {
+        self.input_waits
+    }
+
+    pub fn opcode_count(&self, mnemonic: &str) -> u64 {
+        self.opcodes.get(mnemonic).copied().unwrap_or(0)
+    }
+
+    /// Number of times the instruction at `address` was executed.
+    pub fn address_count(&self, address: usize) -> u64 {
+        self.addresses.get(&address).map_or(0, |&(_, count)| count)
+    }
+
+    /// Number of parameters decoded in `mode`.
+    pub fn mode_count(&self, mode: Mode) -> u64 {
+        self.modes[mode.digit() as usize]
+    }
+
+    pub fn read_count(&self, address: usize) -> u64 {
+        self.reads.get(&address).copied().unwrap_or(0)
+    }
+
+    pub fn write_count(&self, address: usize) -> u64 {
+        self.writes.get(&address).copied().unwrap_or(0)
+    }
+
+    /// Human readable report; the address sections list the `top` entries with the highest counts.
+    pub fn table(&self, top: usize) -> String {
+        let mut table = format!("instructions {}, input waits {}\n", self.instructions, self.input_waits);
+        let opcodes = ranked(self.opcodes.iter().map(|(&mnemonic, &count)| (mnemonic, count)));
+        section(&mut table, "opcode", self.instructions, opcodes.into_iter().map(|(mnemonic, count)| (mnemonic.to_string(), count)));
+        let modes = MODES.iter().map(|&mode| (mode.name().to_string(), self.mode_count(mode)));
+        section(&mut table, "mode", self.modes.iter().sum(), modes);
+        let addresses = ranked(self.addresses.iter().map(|(&address, &(_, count))| (address, count)));
+        section(&mut table, "address", self.instructions, addresses.into_iter().take(top)
+            .map(|(address, count)| (format!("{:>5} {}", address, self.addresses[&address].0), count)));
+        for (title, counts) in [("read", &self.reads), ("write", &self.writes)] {
+            let hot = ranked(counts.iter().map(|(&address, &count)| (address, count)));
+            section(&mut table, title, counts.values().sum(), hot.into_iter().take(top).map(|(address, count)| (format!("{:>5}", address), count)));
+        }
+        table
+    }
+
+    /// The complete profile as a single JSON object. Addresses are listed in ascending order.
+    pub fn to_json(&self) -> String {
+        let mut addresses: Vec<(&usize, &(&str, u64))> = self.addresses.iter().collect();
+        addresses.sort_unstable();
+        let addresses: Vec<String> = addresses.iter()
+            .map(|(address, (mnemonic, count))| format!("{{\"address\":{},\"mnemonic\":{},\"count\":{}}}", address, json_string(mnemonic), count))
+            .collect();
+        format!("{{\"instructions\":{},\"input_waits\":{},\"opcodes\":{},\"modes\":{},\"addresses\":[{}],\"reads\":{},\"writes\":{}}}",
+                self.instructions,
+                self.input_waits,
+                json_object(ranked(self.opcodes.iter().map(|(&mnemonic, &count)| (mnemonic, count))).into_iter()),
+                json_object(MODES.iter().map(|&mode| (mode.name(), self.mode_count(mode)))),
+                addresses.join(","),
+                json_object(by_address(&self.reads).into_iter()),
+                json_object(by_address(&self.writes).into_iter()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run_traced, Context, Status};
+
+    #[test]
+    fn test_counts() {
+        // Counts cell 10 down from 2 to 0, then outputs it.
+        let program = vec!(1001, 10, -1, 10, 1005, 10, 0, 4, 10, 99, 2);
+        let mut profiler = Profiler::new();
+        let mut context = Context::new(program, (), Vec::new());
+        run_traced(&mut context, &mut profiler).unwrap();
+        assert_eq!(profiler.instructions(), 6);
+        assert_eq!((profiler.opcode_count("ADD"), profiler.opcode_count("JNZ"), profiler.opcode_count("OUT")), (2, 2, 1));
+        assert_eq!((profiler.address_count(0), profiler.address_count(9)), (2, 1));
+        assert_eq!((profiler.mode_count(Mode::Position), profiler.mode_count(Mode::Immediate)), (7, 4));
+        assert_eq!((profiler.read_count(10), profiler.write_count(10)), (5, 2));
+
+        let mut profiler = Profiler::new();
+        run_traced(&mut Context::new(vec!(105, 0, 7, 99, 0, 0, 0, 0), (), Vec::new()), &mut profiler).unwrap();
+        assert_eq!(profiler.read_count(7), 0);
+    }
+
+    #[test]
+    fn test_input_waits_and_reports() {
+        let mut profiler = Profiler::new();
+        let mut context = Context::with_memory(vec!(3, 5, 4, 5, 99, 0));
+        assert_eq!(context.resume_traced(&mut profiler), Ok(Status::NeedsInput));
+        context.push_input(7);
+        assert_eq!(context.resume_traced(&mut profiler), Ok(Status::Output(7)));
+        assert_eq!(context.resume_traced(&mut profiler), Ok(Status::Halted));
+        assert_eq!(profiler.input_waits(), 1);
+        assert_eq!(profiler.to_json(), "{\"instructions\":3,\"input_waits\":1,\
+\"opcodes\":{\"HLT\":1,\"IN\":1,\"OUT\":1},\"modes\":{\"position\":2,\"immediate\":0,\"relative\":0},\
+\"addresses\":[{\"address\":0,\"mnemonic\":\"IN\",\"count\":1},{\"address\":2,\"mnemonic\":\"OUT\",\"count\":1},{\"address\":4,\"mnemonic\":\"HLT\",\"count\":1}],\
+\"reads\":{\"5\":1},\"writes\":{\"5\":1}}");
+        assert_eq!(profiler.table(1), "\
+instructions 3, input waits 1
+
+opcode              count   share
+HLT                     1   33.3%
+IN                      1   33.3%
+OUT                     1   33.3%
+
+mode                count   share
+position                2  100.0%
+immediate               0    0.0%
+relative                0    0.0%
+
+address             count   share
+    0 IN                1   33.3%
+
+read                count   share
+    5                   1  100.0%
+
+write               count   share
+    5                   1  100.0%
+");
+    }
+
+    #[test]
+    fn test_json_escapes_mnemonics() {
+        fn nop(_: &mut crate::Operands) -> Result<crate::Flow, crate::IntcodeError> {
+            Ok(crate::Flow::Next)
+        }
+        let mut instructions = crate::InstructionSet::standard();
+        instructions.define(crate::Instruction { opcode: 10, mnemonic: "N\"OP", operand_count: 0, write_operand: None, implementation: nop }).unwrap();
+        let mut context = Context::new(vec!(10, 99), (), Vec::new());
+        context.set_instruction_set(instructions);
+        let mut profiler = Profiler::new();
+        run_traced(&mut context, &mut profiler).unwrap();
+        let json = profiler.to_json();
+        assert!(json.contains("\"opcodes\":{\"HLT\":1,\"N\\\"OP\":1}"), "{}", json);
+        assert!(json.contains("{\"address\":0,\"mnemonic\":\"N\\\"OP\",\"count\":1}"), "{}", json);
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::VecDeque;
+use std::num::NonZeroU64;
+
+use super::{Context, IntcodeError, Status};
+
+/// Index of a machine in a `Scheduler`, in the order the machines were added.
+pub type MachineId = usize;
+
+#[derive(std::fmt::Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MachineState {
+    /// Waiting for its turn.
+    Ready,
+    /// Waiting for input.
+    Blocked,
+    Halted,
+    /// Stopped by a fault, which every later `Scheduler::run` returns again.
+    Failed,
+}
+
+/// Why `Scheduler::run` returned.
+#[derive(std::fmt::Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NetworkStatus {
+    /// Every machine halted.
+    Halted,
+    /// No machine can continue, but some are waiting for input; use `Scheduler::send` to
+    /// continue them.
+    Blocked,
+}
+
+/// A machine of a scheduler faulted.
+#[derive(std::fmt::Debug, PartialEq, Eq, Clone)]
+pub struct SchedulerError {
+    pub machine: MachineId,
+    pub error: IntcodeError,
+}
+
+impl std::fmt::Display for SchedulerError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(fmt, "machine {}: {}", self.machine, self.error)
+    }
+}
+
+#[derive(std::fmt::Debug)]
+struct Node {
+    context: Context,
+    state: MachineState,
+    /// Machines receiving a copy of every output.
+    targets: Vec<MachineId>,
+    /// Outputs of a machine without targets, until taken.
+    outbox: VecDeque<i64>,
+    last_output: Option<i64>,
+    /// Every output, kept for watched machines.
+    log: Option<Vec<i64>>,
+    /// The fault of a failed machine.
+    error: Option<IntcodeError>,
+}
+
+/// Runs a network of machines on the current thread. Machines take turns in a fixed order,
+/// each running until it blocks on input or halts, so runs are deterministic. Outputs are
+/// delivered to the inputs of connected machines, waking them when they are blocked.
+#[derive(std::fmt::Debug, Default)]
+pub struct Scheduler {
+    nodes: Vec<Node>,
+    ready: VecDeque<MachineId>,
+    quantum: Option<NonZeroU64>,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler::default()
+    }
+
+    /// Adds a machine; it gets its first turn after the machines added before it.
+    pub fn add(&mut self, context: Context) -> MachineId {
+        let id = self.nodes.len();
+        self.nodes.push(Node { context, state: MachineState::Ready, targets: Vec::new(), outbox: VecDeque::new(), last_output: None, log: None, error: None });
+        self.ready.push_back(id);
+        id
+    }
+
+    /// Delivers the outputs of `from` to `to`, in addition to earlier connections of `from`.
+    pub fn connect(&mut self, from: MachineId, to: MachineId) {
+        self.nodes[from].targets.push(to);
+    }
+
+    /// Ends the turn of a machine after `quantum` instructions, even when it could continue.
+    /// Without a quantum a machine that never waits for input keeps the others from running.
+    pub fn set_quantum(&mut self, quantum: Option<NonZeroU64>) {
+        self.quantum = quantum;
+    }
+
+    /// Queues input for a machine, waking it when it is blocked.
+    pub fn send(&mut self, to: MachineId, value: i64) {
+        let node = &mut self.nodes[to];
+        node.context.push_input(value);
+        if node.state == MachineState::Blocked {
+            node.state = MachineState::Ready;
+            self.ready.push_back(to);
+        }
+    }
+
+    /// Runs machines until none can continue. Once a machine has failed, its fault is
+    /// returned without running anything.
+    pub fn run(&mut self) -> Result<NetworkStatus, SchedulerError> {
+        for (machine, node) in self.nodes.iter().enumerate() {
+            if let Some(error) = &node.error {
+                return Err(SchedulerError { machine, error: error.clone() });
+            }
+        }
+        while let Some(id) = self.ready.pop_front() {
+            self.turn(id)?;
+        }
+        if self.nodes.iter().all(|node| node.state == MachineState::Halted) {
+            Ok(NetworkStatus::Halted)
+        } else {
+            Ok(NetworkStatus::Blocked)
+        }
+    }
+
+    fn turn(&mut self, id: MachineId) -> Result<(), SchedulerError> {
+        let end = self.quantum.map(|quantum| self.nodes[id].context.executed() + quantum.get());
+        loop {
+            if end.is_some_and(|end| self.nodes[id].context.executed() >= end) {
+                self.ready.push_back(id);
+                return Ok(());
+            }
+            let node = &mut self.nodes[id];
+            match node.context.step() {
+                Ok(None) => {}
+                Ok(Some(Status::Output(value))) => self.deliver(id, value),
+                Ok(Some(Status::NeedsInput)) => {
+                    node.state = MachineState::Blocked;
+                    return Ok(());
+                }
+                Ok(Some(Status::Halted)) => {
+                    node.state = MachineState::Halted;
+                    return Ok(());
+                }
+                Err(error) => {
+                    node.state = MachineState::Failed;
+                    node.error = Some(error.clone());
+                    return Err(SchedulerError { machine: id, error });
+                }
+            }
+        }
+    }
+
+    fn deliver(&mut self, from: MachineId, value: i64) {
+        let node = &mut self.nodes[from];
+        node.last_output = Some(value);
+        if let Some(log) = &mut node.log {
+            log.push(value);
+        }
+        if node.targets.is_empty() {
+            node.outbox.push_back(value);
+        }
+        for index in 0..self.nodes[from].targets.len() {
+            let to = self.nodes[from].targets[index];
+            self.send(to, value);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn state(&self, id: MachineId) -> MachineState {
+        self.nodes[id].state
+    }
+
+    /// Most recent output of a machine, whether it was delivered or not.
+    pub fn last_output(&self, id: MachineId) -> Option<i64> {
+        self.nodes[id].last_output
+    }
+
+    /// Keeps every output of a machine from now on, delivered or not.
+    pub fn watch(&mut self, id: MachineId) {
+        self.nodes[id].log.get_or_insert_with(Vec::new);
+    }
+
+    /// Outputs of a watched machine, empty when it is not watched.
+    pub fn watched(&self, id: MachineId) -> &[i64] {
+        self.nodes[id].log.as_deref().unwrap_or(&[])
+    }
+
+    /// Takes the outputs of a machine without connections.
+    pub fn take_outputs(&mut self, id: MachineId) -> Vec<i64> {
+        self.nodes[id].outbox.drain(..).collect()
+    }
+
+    pub fn context(&self, id: MachineId) -> &Context {
+        &self.nodes[id].context
+    }
+
+    /// The context of a machine; input pushed here does not wake a blocked machine, use `send`.
+    pub fn context_mut(&mut self, id: MachineId) -> &mut Context {
+        &mut self.nodes[id].context
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feedback_loop(program: &[i64], phases: &[i64]) -> Scheduler {
+        let mut scheduler = Scheduler::new();
+        for &phase in phases {
+            let id = scheduler.add(Context::with_memory(program.to_vec()));
+            scheduler.send(id, phase);
+        }
+        for id in 0..phases.len() {
+            scheduler.connect(id, (id + 1) % phases.len());
+        }
+        scheduler.send(0, 0);
+        scheduler
+    }
+
+    #[test]
+    fn test_feedback_loop() {
+        let program = vec!(3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28, 1005, 28, 6, 99, 0, 0, 5);
+        let mut scheduler = feedback_loop(&program, &[9, 8, 7, 6, 5]);
+        assert_eq!(scheduler.run(), Ok(NetworkStatus::Halted));
+        assert_eq!(scheduler.last_output(4), Some(139629729));
+
+        let mut scheduler = feedback_loop(&program, &[9, 8, 7, 6, 5]);
+        scheduler.set_quantum(NonZeroU64::new(3));
+        assert_eq!(scheduler.run(), Ok(NetworkStatus::Halted));
+        assert_eq!(scheduler.last_output(4), Some(139629729));
+
+        // The smallest quantum still makes progress, and 0 means no quantum.
+        for quantum in [1, 0] {
+            let mut scheduler = Scheduler::new();
+            scheduler.add(Context::with_memory(vec!(104, 1, 104, 2, 99)));
+            scheduler.set_quantum(NonZeroU64::new(quantum));
+            assert_eq!(scheduler.run(), Ok(NetworkStatus::Halted));
+            assert_eq!(scheduler.take_outputs(0), vec!(1, 2));
+        }
+    }
+
+    #[test]
+    fn test_blocked_chain_and_errors() {
+        // Outputs its input plus one, forever.
+        let increment = vec!(3, 11, 1001, 11, 1, 11, 4, 11, 1105, 1, 0, 0);
+        let mut scheduler = Scheduler::new();
+        for id in 0..500 {
+            scheduler.add(Context::with_memory(increment.clone()));
+            if id > 0 {
+                scheduler.connect(id - 1, id);
+            }
+        }
+        scheduler.send(0, 1);
+        scheduler.send(0, 1000);
+        assert_eq!(scheduler.run(), Ok(NetworkStatus::Blocked));
+        assert_eq!(scheduler.take_outputs(499), vec!(501, 1500));
+        assert_eq!(scheduler.state(499), MachineState::Blocked);
+
+        let mut scheduler = Scheduler::new();
+        scheduler.add(Context::with_memory(vec!(104, 1, 99)));
+        let failing = scheduler.add(Context::with_memory(vec!(3, 5, 98, 99)));
+        scheduler.connect(0, failing);
+        assert_eq!(scheduler.run(), Err(SchedulerError { machine: 1, error: IntcodeError::UnknownOpcode { ip: 2, opcode: 98 } }));
+        assert_eq!((scheduler.state(0), scheduler.state(1)), (MachineState::Halted, MachineState::Failed));
+        assert_eq!(scheduler.run(), Err(SchedulerError { machine: 1, error: IntcodeError::UnknownOpcode { ip: 2, opcode: 98 } }));
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+use super::{Context, Input, Instruction, IntcodeError, Machine, Output, Parameter};
+
+#[derive(std::fmt::Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfModificationMode {
+    Off,
+    /// Record self modification, see `Context::self_modifications`.
+    Report,
+    /// Fault with `IntcodeError::SelfModifying` before the offending instruction executes.
+    Strict,
+}
+
+/// A program changing its own code. Writes through `Context::write` are not tracked.
+#[derive(std::fmt::Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfModification {
+    /// The instruction at `ip` writes to `address`, a cell of an instruction that was executed
+    /// before or of the instruction itself.
+    Write { ip: usize, address: usize },
+    /// The instruction at `ip` is executed while its cell at `address` was written by the
+    /// program; reported once per write.
+    Execute { ip: usize, address: usize },
+}
+
+impl std::fmt::Display for SelfModification {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            SelfModification::Write { ip, address } => write!(fmt, "instruction at {} writes to code at {}", ip, address),
+            SelfModification::Execute { ip, address } => write!(fmt, "instruction at {} executes cell {} written by the program", ip, address),
+        }
+    }
+}
+
+#[derive(std::fmt::Debug, Clone, Default)]
+pub(crate) struct Tracker {
+    strict: bool,
+    /// Cells of executed instructions.
+    code: HashSet<usize>,
+    /// Cells written by the program and not executed since.
+    written: HashSet<usize>,
+    events: Vec<SelfModification>,
+}
+
+/// Outcome of checking an instruction before it executes, applied once it has executed.
+pub(crate) struct Check {
+    cells: Range<usize>,
+    target: Option<usize>,
+    events: Vec<SelfModification>,
+}
+
+impl Machine {
+    /// Looks for self modification by the instruction at `ip`, which is about to execute.
+    pub(crate) fn check_self_modification(&self, instruction: &Instruction, parameters: &[Parameter]) -> Result<Option<Check>, IntcodeError> {
+        let tracker = match &self.self_modification {
+            Some(tracker) => tracker,
+            None => return Ok(None),
+        };
+        let ip = self.ip;
+        let cells = ip..ip + 1 + parameters.len();
+        let target = instruction.write_operand.and_then(|index| match parameters[index] {
+            Parameter::Absolute(address) => self.locate(address, index).ok().flatten(),
+            Parameter::Relative(offset) => self.relative_base.checked_add(offset).and_then(|address| self.locate(address, index).ok().flatten()),
+            Parameter::Immediate(_) => None,
+        });
+        let mut events = Vec::new();
+        if let Some(address) = cells.clone().find(|cell| tracker.written.contains(cell)) {
+            events.push(SelfModification::Execute { ip, address });
+        }
+        if let Some(address) = target.filter(|address| tracker.code.contains(address) || cells.contains(address)) {
+            events.push(SelfModification::Write { ip, address });
+        }
+        if tracker.strict && !events.is_empty() {
+            return Err(IntcodeError::SelfModifying { ip, opcode: self.opcode(), modification: events[0] });
+        }
+        Ok(Some(Check { cells, target, events }))
+    }
+
+    /// Records the outcome of a check once its instruction has executed.
+    pub(crate) fn apply_self_modification(&mut self, check: Check) {
+        if let Some(tracker) = &mut self.self_modification {
+            for cell in check.cells.clone() {
+                tracker.written.remove(&cell);
+            }
+            tracker.code.extend(check.cells);
+            tracker.written.extend(check.target);
+            tracker.events.extend(check.events);
+        }
+    }
+}
+
+impl<I: Input, O: Output> Context<I, O> {
+    /// Starts or stops tracking self modification. Changing between `Report` and `Strict`
+    /// keeps what was tracked so far.
+    pub fn set_self_modification_mode(&mut self, mode: SelfModificationMode) {
+        match (mode, &mut self.machine.self_modification) {
+            (SelfModificationMode::Off, tracker) => *tracker = None,
+            (mode, Some(tracker)) => tracker.strict = mode == SelfModificationMode::Strict,
+            (mode, tracker) => *tracker = Some(Box::new(Tracker { strict: mode == SelfModificationMode::Strict, ..Tracker::default() })),
+        }
+    }
+
+    /// Self modification found since tracking started, in order of execution.
+    pub fn self_modifications(&self) -> &[SelfModification] {
+        self.machine.self_modification.as_ref().map_or(&[], |tracker| &tracker.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run, Status};
+
+    #[test]
+    fn test_report() {
+        // Sets the condition of the JNZ at 4 to 1 before executing it, then writes over the ADD at 0.
+        let mut context = Context::with_memory(vec!(1101, 0, 1, 5, 1105, 0, 8, 99, 1101, 0, 0, 0, 99));
+        context.set_self_modification_mode(SelfModificationMode::Report);
+        run(&mut context).unwrap();
+        assert_eq!(context.self_modifications(), &[
+            SelfModification::Execute { ip: 4, address: 5 },
+            SelfModification::Write { ip: 8, address: 0 }]);
+    }
+
+    #[test]
+    fn test_strict() {
+        let mut context = Context::with_memory(vec!(1101, 1, 1, 0, 99));
+        context.set_self_modification_mode(SelfModificationMode::Strict);
+        assert_eq!(context.resume(), Err(IntcodeError::SelfModifying {
+            ip: 0, opcode: 1101, modification: SelfModification::Write { ip: 0, address: 0 } }));
+        assert_eq!(context.memory().to_vec(), vec!(1101, 1, 1, 0, 99));
+
+        let mut context = Context::with_memory(vec!(1101, 1, 1, 0, 99));
+        context.set_self_modification_mode(SelfModificationMode::Off);
+        assert_eq!(context.resume(), Ok(Status::Halted));
+        assert!(context.self_modifications().is_empty());
+    }
+}
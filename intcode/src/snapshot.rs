@@ -0,0 +1,262 @@
+use std::collections::VecDeque;
+use std::path::Path;
+
+use super::{Backend, Context, Input, Machine, Memory, Output};
+
+const HEADER: &str = "intcode-snapshot 1";
+
+/// Complete state of a paused machine. Input source and output sink are not part of it;
+/// they are supplied again when the snapshot is restored.
+///
+/// The text form is line based and stable across versions of this crate:
+///
+/// ```text
+/// intcode-snapshot 1
+/// ip 12
+/// relative_base 0
+/// input 5,7
+/// memory 1,2,3,99
+/// ```
+///
+/// Sparse memory is written as its length and runs of cells, each at its address; the
+/// cells outside the runs are 0:
+///
+/// ```text
+/// memory
+/// length 1000000000001
+/// at 0 3,1000000000000,99
+/// at 1000000000000 7
+/// ```
+#[derive(std::fmt::Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Restored with the same backend.
+    pub memory: Memory,
+    pub ip: usize,
+    pub relative_base: i64,
+    /// Values pushed with `Context::push_input` that the program has not read yet.
+    pub pending_input: Vec<i64>,
+}
+
+#[derive(std::fmt::Debug, PartialEq, Eq, Clone)]
+pub enum SnapshotError {
+    UnsupportedHeader { header: String },
+    Syntax { line: usize, message: String },
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            SnapshotError::UnsupportedHeader { header } =>
+                write!(fmt, "unsupported snapshot header {:?}", header),
+            SnapshotError::Syntax { line, message } =>
+                write!(fmt, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl Snapshot {
+    /// Writes the text form to `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    /// Reads a snapshot saved with `save`. Malformed files are reported as `InvalidData`.
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Snapshot> {
+        std::fs::read_to_string(path)?
+            .parse()
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+    }
+}
+
+fn join(values: &[i64]) -> String {
+    values.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(",")
+}
+
+impl std::fmt::Display for Snapshot {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        writeln!(fmt, "{}", HEADER)?;
+        writeln!(fmt, "ip {}", self.ip)?;
+        writeln!(fmt, "relative_base {}", self.relative_base)?;
+        writeln!(fmt, "{}", format!("input {}", join(&self.pending_input)).trim_end())?;
+        if self.memory.backend() == Backend::Dense {
+            return writeln!(fmt, "{}", format!("memory {}", join(&self.memory.to_vec())).trim_end());
+        }
+        writeln!(fmt, "memory")?;
+        writeln!(fmt, "length {}", self.memory.len())?;
+        for (address, run) in self.memory.runs() {
+            writeln!(fmt, "at {} {}", address, join(&run))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Snapshot {
+    type Err = SnapshotError;
+
+    fn from_str(text: &str) -> Result<Snapshot, SnapshotError> {
+        let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line.trim_end()));
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            Some((_, header)) => return Err(SnapshotError::UnsupportedHeader { header: header.to_string() }),
+            None => return Err(SnapshotError::UnsupportedHeader { header: String::new() }),
+        }
+        let mut field = |name: &str| -> Result<(usize, String), SnapshotError> {
+            let (line, text) = lines.next()
+                .ok_or_else(|| SnapshotError::Syntax { line: 0, message: format!("missing {}", name) })?;
+            match text.split_once(' ').unwrap_or((text, "")) {
+                (key, value) if key == name => Ok((line, value.trim().to_string())),
+                (key, _) => Err(SnapshotError::Syntax { line, message: format!("expected {}, found {}", name, key) }),
+            }
+        };
+        fn number<T: std::str::FromStr>(line: usize, text: &str) -> Result<T, SnapshotError> {
+            text.parse().map_err(|_| SnapshotError::Syntax { line, message: format!("invalid number {:?}", text) })
+        }
+        fn numbers(line: usize, text: &str) -> Result<Vec<i64>, SnapshotError> {
+            if text.is_empty() {
+                return Ok(Vec::new());
+            }
+            text.split(',').map(|value| number(line, value.trim())).collect()
+        }
+
+        let (line, ip) = field("ip")?;
+        let ip = number(line, &ip)?;
+        let (line, relative_base) = field("relative_base")?;
+        let relative_base = number(line, &relative_base)?;
+        let (line, pending_input) = field("input")?;
+        let pending_input = numbers(line, &pending_input)?;
+        let (line, memory) = field("memory")?;
+        let mut memory = Memory::from(numbers(line, &memory)?);
+        if let Some((line, length)) = lines.next() {
+            let length = match length.split_once(' ') {
+                Some(("length", length)) if memory.is_empty() => number(line, length.trim())?,
+                _ => return Err(SnapshotError::Syntax { line, message: "expected length after an empty memory".to_string() }),
+            };
+            memory = memory.with_backend(Backend::Sparse);
+            memory.grow(length);
+            for (line, text) in lines {
+                let (address, run) = match text.split_once(' ') {
+                    Some(("at", run)) => run.trim().split_once(' ').unwrap_or((run.trim(), "")),
+                    _ => return Err(SnapshotError::Syntax { line, message: "expected at".to_string() }),
+                };
+                let address: usize = number(line, address)?;
+                let run = numbers(line, run.trim())?;
+                if address.checked_add(run.len()).is_none_or(|end| end > length) {
+                    return Err(SnapshotError::Syntax { line, message: "cells beyond the length of memory".to_string() });
+                }
+                for (offset, value) in run.into_iter().enumerate().filter(|&(_, value)| value != 0) {
+                    memory.write(address + offset, value);
+                }
+            }
+        }
+        Ok(Snapshot { memory, ip, relative_base, pending_input })
+    }
+}
+
+impl<I: Input, O: Output> Context<I, O> {
+    /// Captures the machine state. Take snapshots between calls to `step` or `resume`.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.machine.memory.clone(),
+            ip: self.machine.ip,
+            relative_base: self.machine.relative_base,
+            pending_input: self.machine.pending_input.iter().copied().collect(),
+        }
+    }
+
+    /// Creates a context that continues where the snapshot was taken. Only the state in the
+    /// snapshot is restored. Everything else starts out as in `Context::new`: the memory model
+    /// apart from the backend, the limits, the count of executed instructions, the arithmetic policy, the instruction
+    /// set and self modification tracking. Set them again on the restored context where needed.
+    pub fn restore(snapshot: Snapshot, input: I, output: O) -> Context<I, O> {
+        let mut machine = Machine::new(Vec::new());
+        machine.memory_config.backend = snapshot.memory.backend();
+        machine.memory = snapshot.memory;
+        machine.ip = snapshot.ip;
+        machine.relative_base = snapshot.relative_base;
+        machine.pending_input = VecDeque::from(snapshot.pending_input);
+        Context { machine, input, output }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ArithmeticPolicy, Flow, Instruction, InstructionSet, IntcodeError, Limits, MemoryConfig, Operands, Status};
+
+    #[test]
+    fn test_restore_continues_run() {
+        // Adjusts the relative base, reads two values and outputs their sum.
+        let mut context = Context::with_memory(vec!(109, 5, 3, 20, 3, 21, 1, 20, 21, 22, 4, 22, 99));
+        context.push_input(3);
+        context.push_input(4);
+        context.step().unwrap();
+        context.step().unwrap();
+
+        let snapshot: Snapshot = context.snapshot().to_string().parse().unwrap();
+        assert_eq!(snapshot.ip, 4);
+        assert_eq!(snapshot.relative_base, 5);
+        assert_eq!(snapshot.pending_input, vec!(4));
+
+        let mut restored = Context::restore(snapshot, (), ());
+        assert_eq!(restored.resume(), Ok(Status::Output(7)));
+        assert_eq!(context.resume(), Ok(Status::Output(7)));
+        assert_eq!(restored.memory(), context.memory());
+    }
+
+    #[test]
+    fn test_restore_resets_configuration() {
+        fn nop(_operands: &mut Operands) -> Result<Flow, IntcodeError> {
+            Ok(Flow::Next)
+        }
+        let mut instructions = InstructionSet::standard();
+        instructions.define(Instruction { opcode: 10, mnemonic: "NOP", operand_count: 0, write_operand: None, implementation: nop }).unwrap();
+        let mut context = Context::with_memory(vec!(10, 10, 99));
+        context.set_instruction_set(instructions);
+        context.set_arithmetic_policy(ArithmeticPolicy::Checked);
+        context.set_limits(Limits { max_instructions: Some(100), ..Limits::default() });
+        context.set_memory_config(MemoryConfig { address_limit: Some(8), ..MemoryConfig::default() });
+        context.step().unwrap();
+
+        let mut restored = Context::restore(context.snapshot(), (), ());
+        assert_eq!(restored.ip(), 1);
+        assert_eq!((restored.executed(), restored.limits(), restored.memory_config(), restored.arithmetic_policy()),
+                   (0, &Limits::default(), &MemoryConfig::default(), ArithmeticPolicy::Wrapping));
+        assert_eq!(restored.resume(), Err(IntcodeError::UnknownOpcode { ip: 1, opcode: 10 }));
+    }
+
+    #[test]
+    fn test_sparse_memory() {
+        let mut context = Context::with_memory(vec!(3, 1_000_000_000_000, 99));
+        context.set_memory_config(MemoryConfig { backend: Backend::Sparse, ..MemoryConfig::default() });
+        context.push_input(7);
+        context.step().unwrap();
+
+        let text = context.snapshot().to_string();
+        assert_eq!(text, "intcode-snapshot 1\nip 2\nrelative_base 0\ninput\nmemory\nlength 1000000000001\nat 0 3,1000000000000,99\nat 1000000000000 7\n");
+        let snapshot: Snapshot = text.parse().unwrap();
+        assert_eq!(snapshot, context.snapshot());
+        let restored = Context::restore(snapshot, (), ());
+        assert_eq!(restored.memory_config().backend, Backend::Sparse);
+        assert_eq!((restored.memory().get(1_000_000_000_000), restored.memory().allocated()), (Some(7), 512));
+
+        assert_eq!("intcode-snapshot 1\nip 0\nrelative_base 0\ninput\nmemory\nlength 10\nat 8 1,2,3\n".parse::<Snapshot>(),
+                   Err(SnapshotError::Syntax { line: 7, message: "cells beyond the length of memory".to_string() }));
+    }
+
+    #[test]
+    fn test_text_form() {
+        let snapshot = Snapshot { memory: Memory::from(vec!(1, -2, 99)), ip: 2, relative_base: -3, pending_input: vec!() };
+        let text = "intcode-snapshot 1\nip 2\nrelative_base -3\ninput\nmemory 1,-2,99\n";
+        assert_eq!(snapshot.to_string(), text);
+        assert_eq!(text.parse(), Ok(snapshot));
+
+        assert_eq!("intcode-snapshot 2\n".parse::<Snapshot>(),
+                   Err(SnapshotError::UnsupportedHeader { header: "intcode-snapshot 2".to_string() }));
+        assert_eq!("intcode-snapshot 1\nip x\n".parse::<Snapshot>(),
+                   Err(SnapshotError::Syntax { line: 2, message: "invalid number \"x\"".to_string() }));
+        assert_eq!("intcode-snapshot 1\nip 0\nmemory 1\n".parse::<Snapshot>(),
+                   Err(SnapshotError::Syntax { line: 3, message: "expected relative_base, found memory".to_string() }));
+    }
+}
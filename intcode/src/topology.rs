@@ -0,0 +1,175 @@
+use super::{Context, MachineId, NetworkStatus, Scheduler, SchedulerError};
+
+/// Description of a network of machines: the machines with their initial input, how their
+/// outputs are connected and named taps to observe outputs. `build` wires it into a `Network`.
+#[derive(std::fmt::Debug, Clone, Default)]
+pub struct Topology {
+    machines: Vec<(Vec<i64>, Vec<i64>)>,
+    connections: Vec<(MachineId, MachineId)>,
+    taps: Vec<(String, MachineId)>,
+}
+
+impl Topology {
+    pub fn new() -> Topology {
+        Topology::default()
+    }
+
+    /// Adds a machine running `program`, receiving `inputs` before any connected output.
+    pub fn machine(&mut self, program: &[i64], inputs: &[i64]) -> MachineId {
+        self.machines.push((program.to_vec(), inputs.to_vec()));
+        self.machines.len() - 1
+    }
+
+    /// Adds a machine running `program` for every entry of `inputs`, such as phase settings.
+    pub fn machines(&mut self, program: &[i64], inputs: &[Vec<i64>]) -> Vec<MachineId> {
+        inputs.iter().map(|inputs| self.machine(program, inputs)).collect()
+    }
+
+    /// Appends to the initial input of a machine.
+    pub fn input(&mut self, id: MachineId, inputs: &[i64]) -> &mut Topology {
+        self.machines[id].1.extend_from_slice(inputs);
+        self
+    }
+
+    pub fn connect(&mut self, from: MachineId, to: MachineId) -> &mut Topology {
+        self.connections.push((from, to));
+        self
+    }
+
+    /// Connects every machine to the next one.
+    pub fn chain(&mut self, ids: &[MachineId]) -> &mut Topology {
+        for pair in ids.windows(2) {
+            self.connect(pair[0], pair[1]);
+        }
+        self
+    }
+
+    /// Connects every machine to the next one and the last one back to the first.
+    pub fn cycle(&mut self, ids: &[MachineId]) -> &mut Topology {
+        self.chain(ids);
+        if let (Some(&first), Some(&last)) = (ids.first(), ids.last()) {
+            self.connect(last, first);
+        }
+        self
+    }
+
+    /// Sends a copy of every output of `from` to each of `to`.
+    pub fn fan_out(&mut self, from: MachineId, to: &[MachineId]) -> &mut Topology {
+        for &to in to {
+            self.connect(from, to);
+        }
+        self
+    }
+
+    /// Merges the outputs of `from` into the input of `to`, in the order they are produced.
+    pub fn fan_in(&mut self, from: &[MachineId], to: MachineId) -> &mut Topology {
+        for &from in from {
+            self.connect(from, to);
+        }
+        self
+    }
+
+    /// Names the outputs of a machine; a name used before is moved to `id`.
+    pub fn tap(&mut self, name: &str, id: MachineId) -> &mut Topology {
+        self.taps.retain(|(tap, _)| tap != name);
+        self.taps.push((name.to_string(), id));
+        self
+    }
+
+    pub fn build(&self) -> Network {
+        let mut scheduler = Scheduler::new();
+        for (program, _) in &self.machines {
+            scheduler.add(Context::with_memory(program.clone()));
+        }
+        for (id, (_, inputs)) in self.machines.iter().enumerate() {
+            for &value in inputs {
+                scheduler.send(id, value);
+            }
+        }
+        for &(from, to) in &self.connections {
+            scheduler.connect(from, to);
+        }
+        for &(_, id) in &self.taps {
+            scheduler.watch(id);
+        }
+        Network { scheduler, taps: self.taps.clone() }
+    }
+}
+
+/// A topology wired into a scheduler.
+#[derive(std::fmt::Debug)]
+pub struct Network {
+    scheduler: Scheduler,
+    taps: Vec<(String, MachineId)>,
+}
+
+impl Network {
+    pub fn run(&mut self) -> Result<NetworkStatus, SchedulerError> {
+        self.scheduler.run()
+    }
+
+    /// Every output seen at a tap. Panics when there is no tap named `name`.
+    pub fn tap(&self, name: &str) -> &[i64] {
+        self.scheduler.watched(self.tap_id(name))
+    }
+
+    /// The last output seen at a tap, the final output once the network halted.
+    pub fn last(&self, name: &str) -> Option<i64> {
+        self.tap(name).last().copied()
+    }
+
+    fn tap_id(&self, name: &str) -> MachineId {
+        match self.taps.iter().find(|(tap, _)| tap == name) {
+            Some(&(_, id)) => id,
+            None => panic!("no tap named {}", name),
+        }
+    }
+
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    pub fn scheduler_mut(&mut self) -> &mut Scheduler {
+        &mut self.scheduler
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Outputs its first input plus ten times its second input.
+    const AMPLIFIER: [i64; 17] = [3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0];
+
+    #[test]
+    fn test_chain_with_any_length() {
+        let mut topology = Topology::new();
+        let stages = topology.machines(&AMPLIFIER, &[vec!(1), vec!(2), vec!(3), vec!(4), vec!(5), vec!(6), vec!(7)]);
+        topology.input(stages[0], &[0]).chain(&stages).tap("out", stages[6]);
+        let mut network = topology.build();
+        assert_eq!(network.run(), Ok(NetworkStatus::Halted));
+        assert_eq!(network.last("out"), Some(1234567));
+
+        let mut topology = Topology::new();
+        let stages = topology.machines(&AMPLIFIER, &[vec!(4, 0), vec!(3)]);
+        topology.cycle(&stages).tap("out", stages[1]);
+        let mut network = topology.build();
+        assert_eq!(network.run(), Ok(NetworkStatus::Halted));
+        assert_eq!(network.tap("out"), &[43]);
+    }
+
+    #[test]
+    fn test_fan_out_and_fan_in() {
+        let mut topology = Topology::new();
+        let source = topology.machine(&[104, 7, 99], &[]);
+        let left = topology.machine(&AMPLIFIER, &[1]);
+        let right = topology.machine(&AMPLIFIER, &[2]);
+        // Echoes two inputs.
+        let sink = topology.machine(&[3, 9, 4, 9, 3, 9, 4, 9, 99, 0], &[]);
+        topology.fan_out(source, &[left, right]).fan_in(&[left, right], sink).tap("sink", sink).tap("source", source);
+        let mut network = topology.build();
+        assert_eq!(network.run(), Ok(NetworkStatus::Halted));
+        assert_eq!(network.tap("sink"), &[71, 72]);
+        assert_eq!(network.last("source"), Some(7));
+    }
+}
@@ -0,0 +1,212 @@
+use std::io::Write;
+
+use super::Mode;
+
+/// A parameter as it was resolved while executing an instruction.
+#[derive(std::fmt::Debug, Clone, PartialEq, Eq)]
+pub struct TracedParameter {
+    pub mode: Mode,
+    /// The parameter as stored in the program.
+    pub raw: i64,
+    /// The memory cell that was accessed; `None` for immediate parameters.
+    pub address: Option<usize>,
+    /// The value read, or for a write the value written.
+    pub value: i64,
+    /// The instruction read the cell; a jump that is not taken leaves its target unread.
+    pub read: bool,
+    pub write: bool,
+}
+
+impl std::fmt::Display for TracedParameter {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match (self.mode, self.address) {
+            (Mode::Immediate, _) | (_, None) => write!(fmt, "#{}", self.value),
+            (mode, Some(address)) => {
+                if mode == Mode::Relative {
+                    write!(fmt, "@{}", self.raw)?;
+                }
+                write!(fmt, "[{}]{}{}", address, if self.write { "<-" } else { "=" }, self.value)
+            }
+        }
+    }
+}
+
+/// One executed instruction. Read parameters hold the value before the instruction executed,
+/// the written parameter the value it wrote.
+#[derive(std::fmt::Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub ip: usize,
+    pub opcode: i64,
+    pub mnemonic: &'static str,
+    pub parameters: Vec<TracedParameter>,
+    /// The relative base in effect while the instruction executed.
+    pub relative_base: i64,
+}
+
+impl TraceRecord {
+    /// The memory cell written by the instruction, with the value written.
+    pub fn write(&self) -> Option<(usize, i64)> {
+        self.parameters.iter()
+            .find(|parameter| parameter.write)
+            .and_then(|parameter| parameter.address.map(|address| (address, parameter.value)))
+    }
+
+    pub fn to_json(&self) -> String {
+        let parameters: Vec<String> = self.parameters.iter().map(|parameter| format!(
+            "{{\"mode\":\"{}\",\"raw\":{},\"address\":{},\"value\":{},\"write\":{}}}",
+            parameter.mode.name(),
+            parameter.raw,
+            parameter.address.map_or("null".to_string(), |address| address.to_string()),
+            parameter.value,
+            parameter.write)).collect();
+        format!("{{\"ip\":{},\"opcode\":{},\"mnemonic\":{},\"parameters\":[{}],\"relative_base\":{}}}",
+                self.ip, self.opcode, json_string(self.mnemonic), parameters.join(","), self.relative_base)
+    }
+}
+
+/// `text` as a quoted JSON string. Mnemonics of custom instructions can hold any character.
+pub(crate) fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Compact single line form: `    12: ADD  [100]=3 #1 [100]<-4 rb=0`.
+impl std::fmt::Display for TraceRecord {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(fmt, "{:>6}: {:<4}", self.ip, self.mnemonic)?;
+        for parameter in &self.parameters {
+            write!(fmt, " {}", parameter)?;
+        }
+        write!(fmt, " rb={}", self.relative_base)
+    }
+}
+
+/// Receives a record for every instruction executed by `run_traced`, `Context::resume_traced`
+/// or `Context::step_traced`.
+pub trait Tracer {
+    fn trace(&mut self, record: &TraceRecord);
+
+    /// Called when the instruction at `ip` needs input that neither the queue nor the input
+    /// source can provide, so control returns to the caller without executing it.
+    fn wait(&mut self, _ip: usize) {}
+}
+
+/// Collects the records in memory.
+impl Tracer for Vec<TraceRecord> {
+    fn trace(&mut self, record: &TraceRecord) {
+        self.push(record.clone());
+    }
+}
+
+/// Writes one JSON object per line. Tracing stops at the first write error, which is
+/// reported by `finish`.
+pub struct JsonLinesTracer<W: Write> {
+    writer: W,
+    error: Option<std::io::Error>,
+}
+
+impl<W: Write> JsonLinesTracer<W> {
+    pub fn new(writer: W) -> JsonLinesTracer<W> {
+        JsonLinesTracer { writer, error: None }
+    }
+
+    pub fn finish(mut self) -> std::io::Result<W> {
+        match self.error {
+            Some(error) => Err(error),
+            None => self.writer.flush().map(|_| self.writer),
+        }
+    }
+}
+
+impl<W: Write> Tracer for JsonLinesTracer<W> {
+    fn trace(&mut self, record: &TraceRecord) {
+        if self.error.is_none() {
+            self.error = writeln!(self.writer, "{}", record.to_json()).err();
+        }
+    }
+}
+
+/// Writes the compact human readable form, one instruction per line. Tracing stops at the
+/// first write error, which is reported by `finish`.
+pub struct TextTracer<W: Write> {
+    writer: W,
+    error: Option<std::io::Error>,
+}
+
+impl<W: Write> TextTracer<W> {
+    pub fn new(writer: W) -> TextTracer<W> {
+        TextTracer { writer, error: None }
+    }
+
+    pub fn finish(mut self) -> std::io::Result<W> {
+        match self.error {
+            Some(error) => Err(error),
+            None => self.writer.flush().map(|_| self.writer),
+        }
+    }
+}
+
+impl<W: Write> Tracer for TextTracer<W> {
+    fn trace(&mut self, record: &TraceRecord) {
+        if self.error.is_none() {
+            self.error = writeln!(self.writer, "{}", record).err();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run_traced, Context};
+
+    #[test]
+    fn test_trace_records() {
+        let mut trace = Vec::new();
+        let mut context = Context::new(vec!(109, 3, 21101, 2, 3, 7, 204, 7, 99), (), Vec::new());
+        run_traced(&mut context, &mut trace).unwrap();
+        assert_eq!(trace.len(), 4);
+        assert_eq!(trace[1], TraceRecord {
+            ip: 2,
+            opcode: 21101,
+            mnemonic: "ADD",
+            parameters: vec!(
+                TracedParameter { mode: Mode::Immediate, raw: 2, address: None, value: 2, read: false, write: false },
+                TracedParameter { mode: Mode::Immediate, raw: 3, address: None, value: 3, read: false, write: false },
+                TracedParameter { mode: Mode::Relative, raw: 7, address: Some(10), value: 5, read: false, write: true }),
+            relative_base: 3,
+        });
+        assert_eq!(trace[1].write(), Some((10, 5)));
+        assert_eq!(context.output(), &vec!(5));
+    }
+
+    #[test]
+    fn test_text_and_json_lines() {
+        let mut text = TextTracer::new(Vec::new());
+        run_traced(&mut Context::with_memory(vec!(1001, 5, 1, 5, 99, 41)), &mut text).unwrap();
+        assert_eq!(String::from_utf8(text.finish().unwrap()).unwrap(), "     0: ADD  [5]=41 #1 [5]<-42 rb=0\n     4: HLT  rb=0\n");
+
+        let mut json = JsonLinesTracer::new(Vec::new());
+        run_traced(&mut Context::with_memory(vec!(204, -1, 99)), &mut json).unwrap_err();
+        assert_eq!(String::from_utf8(json.finish().unwrap()).unwrap(), "");
+
+        let mut json = JsonLinesTracer::new(Vec::new());
+        run_traced(&mut Context::with_memory(vec!(1105, 0, 7, 99)), &mut json).unwrap();
+        assert_eq!(String::from_utf8(json.finish().unwrap()).unwrap(), "\
+{\"ip\":0,\"opcode\":1105,\"mnemonic\":\"JNZ\",\"parameters\":[{\"mode\":\"immediate\",\"raw\":0,\"address\":null,\"value\":0,\"write\":false},{\"mode\":\"immediate\",\"raw\":7,\"address\":null,\"value\":7,\"write\":false}],\"relative_base\":0}
+{\"ip\":3,\"opcode\":99,\"mnemonic\":\"HLT\",\"parameters\":[],\"relative_base\":0}
+");
+
+        let record = TraceRecord { ip: 0, opcode: 10, mnemonic: "A\"B\\C\n", parameters: vec!(), relative_base: 0 };
+        assert_eq!(record.to_json(), "{\"ip\":0,\"opcode\":10,\"mnemonic\":\"A\\\"B\\\\C\\u000a\",\"parameters\":[],\"relative_base\":0}");
+    }
+}